@@ -0,0 +1,211 @@
+//! Derive macro for `instancebuilder::FromInstanceBuilder`.
+//!
+//! This crate is not meant to be used directly, enable the `derive` feature of `instancebuilder`
+//! instead.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Field, Fields, GenericArgument, PathArguments, Type,
+};
+
+/// Derives `FromInstanceBuilder` for a struct by resolving every field from the builder.
+///
+/// The way a field gets resolved is controlled by the `#[instance(...)]` attribute:
+///
+/// * `#[instance(build)]` builds the field type via `builder.build()`. This is the default for
+///   fields without attribute.
+/// * `#[instance(data)]` clones the field type out of `builder.data()`.
+/// * `#[instance(optional)]` expects an `Option<T>` field and clones `T` out of
+///   `builder.data_opt()`.
+/// * `#[instance(default)]` initializes the field with `Default::default()`.
+///
+/// All fields are resolved before an error is returned, multiple failures are combined into a
+/// `BuilderError::Multiple`. The resolved fields are declared as `dependencies()`.
+#[proc_macro_derive(FromInstanceBuilder, attributes(instance))]
+pub fn derive_from_instance_builder(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Build,
+    Data,
+    Optional,
+    Default,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(syn::Error::new(
+                data.enum_token.span,
+                "FromInstanceBuilder can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "FromInstanceBuilder can only be derived for structs",
+            ))
+        }
+    };
+
+    let values = fields
+        .iter()
+        .map(field_value)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let dependencies = fields
+        .iter()
+        .map(field_dependency)
+        .collect::<syn::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten();
+    let bindings = (0..values.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect::<Vec<_>>();
+
+    let body = match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|field| &field.ident);
+            quote! { Self { #(#idents: #bindings,)* } }
+        }
+        Fields::Unnamed(_) => quote! { Self( #(#bindings,)* ) },
+        Fields::Unit => quote! { Self },
+    };
+
+    // All fields are resolved before failing, so every missing dependency gets reported.
+    let resolve = if values.is_empty() {
+        quote! { ::std::result::Result::Ok(#body) }
+    } else {
+        quote! {
+            match ( #(#values,)* ) {
+                    ( #(::std::result::Result::Ok(#bindings),)* ) => ::std::result::Result::Ok(#body),
+                    ( #(#bindings,)* ) => ::std::result::Result::Err(
+                        ::instancebuilder::BuilderError::from_errors(
+                            [ #(#bindings.err(),)* ].into_iter().flatten(),
+                        ),
+                    ),
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::instancebuilder::FromInstanceBuilder for #name #ty_generics #where_clause {
+            fn try_from_builder(
+                builder: &::instancebuilder::InstanceBuilder<'_>,
+            ) -> ::std::result::Result<Self, ::instancebuilder::BuilderError> {
+                #resolve
+            }
+
+            fn dependencies() -> ::std::vec::Vec<::instancebuilder::Dependency> {
+                ::std::vec![ #(#dependencies,)* ]
+            }
+        }
+    })
+}
+
+/// Returns the expression resolving the field, evaluating to a `Result` of the field type.
+fn field_value(field: &Field) -> syn::Result<TokenStream2> {
+    let ty = &field.ty;
+    let span = ty.span();
+
+    Ok(match field_kind(field)? {
+        Kind::Build => quote_spanned! {span=> builder.build::<#ty>() },
+        Kind::Data => quote_spanned! {span=> builder.data::<#ty>().cloned() },
+        Kind::Optional => {
+            let inner = option_inner(ty).ok_or_else(|| {
+                syn::Error::new(
+                    span,
+                    "#[instance(optional)] requires a field of type Option<T>",
+                )
+            })?;
+            quote_spanned! {span=>
+                ::std::result::Result::<_, ::instancebuilder::BuilderError>::Ok(
+                    builder.data_opt::<#inner>().cloned(),
+                )
+            }
+        }
+        Kind::Default => quote_spanned! {span=>
+            ::std::result::Result::<#ty, ::instancebuilder::BuilderError>::Ok(
+                ::std::default::Default::default(),
+            )
+        },
+    })
+}
+
+/// Returns the declared dependency of the field, default fields have none.
+fn field_dependency(field: &Field) -> syn::Result<Option<TokenStream2>> {
+    let ty = &field.ty;
+    let span = ty.span();
+
+    Ok(match field_kind(field)? {
+        Kind::Build => Some(quote_spanned! {span=> ::instancebuilder::Dependency::build::<#ty>() }),
+        Kind::Data => Some(quote_spanned! {span=> ::instancebuilder::Dependency::data::<#ty>() }),
+        Kind::Optional => {
+            // Validated by `field_value`.
+            let inner = option_inner(ty).unwrap_or(ty);
+            Some(quote_spanned! {span=> ::instancebuilder::Dependency::optional::<#inner>() })
+        }
+        Kind::Default => None,
+    })
+}
+
+fn field_kind(field: &Field) -> syn::Result<Kind> {
+    let mut kind = None;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("instance")) {
+        attr.parse_nested_meta(|meta| {
+            let parsed = if meta.path.is_ident("build") {
+                Kind::Build
+            } else if meta.path.is_ident("data") {
+                Kind::Data
+            } else if meta.path.is_ident("optional") {
+                Kind::Optional
+            } else if meta.path.is_ident("default") {
+                Kind::Default
+            } else {
+                return Err(meta.error("expected one of `build`, `data`, `optional` or `default`"));
+            };
+
+            if kind.replace(parsed).is_some() {
+                return Err(meta.error("only one resolution kind is allowed per field"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(kind.unwrap_or(Kind::Build))
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,149 @@
+use instancebuilder::{BuilderError, DependencyKind, FromInstanceBuilder, InstanceBuilder};
+
+#[derive(Clone)]
+struct TestConfig {
+    key: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Timeout(u64);
+
+#[derive(FromInstanceBuilder)]
+struct InnerTestImplementation {
+    #[instance(data)]
+    config: TestConfig,
+}
+
+#[derive(FromInstanceBuilder)]
+struct OuterTestImplementation {
+    inner: InnerTestImplementation,
+    #[instance(optional)]
+    timeout: Option<Timeout>,
+    #[instance(default)]
+    counter: usize,
+}
+
+#[derive(FromInstanceBuilder)]
+struct TupleImplementation(
+    #[instance(data)] TestConfig,
+    #[instance(build)] InnerTestImplementation,
+);
+
+#[derive(FromInstanceBuilder)]
+struct UnitImplementation;
+
+fn builder() -> InstanceBuilder<'static> {
+    let mut builder = InstanceBuilder::new();
+    builder.insert(TestConfig {
+        key: String::from("help me!"),
+    });
+    builder
+}
+
+#[test]
+fn it_derives_data_build_optional_and_default_fields() {
+    let mut builder = builder();
+    builder.insert(Timeout(30));
+
+    let instance = builder.build::<OuterTestImplementation>().unwrap();
+
+    assert_eq!(instance.inner.config.key, "help me!");
+    assert_eq!(instance.timeout, Some(Timeout(30)));
+    assert_eq!(instance.counter, 0);
+}
+
+#[test]
+fn it_leaves_missing_optional_fields_empty() {
+    let instance = builder().build::<OuterTestImplementation>().unwrap();
+
+    assert_eq!(instance.timeout, None);
+}
+
+#[test]
+fn it_derives_tuple_and_unit_structs() {
+    let builder = builder();
+
+    let instance = builder.build::<TupleImplementation>().unwrap();
+    assert_eq!(instance.0.key, "help me!");
+    assert_eq!(instance.1.config.key, "help me!");
+
+    builder.build::<UnitImplementation>().unwrap();
+}
+
+#[test]
+fn it_reports_missing_data() {
+    let builder = InstanceBuilder::new();
+
+    let err = builder.build::<OuterTestImplementation>().err().unwrap();
+
+    assert!(matches!(
+        err.root(),
+        BuilderError::DataDoesNotExist { ty } if ty == std::any::type_name::<TestConfig>()
+    ));
+}
+
+#[derive(FromInstanceBuilder)]
+struct ServiceWithManyDependencies {
+    #[instance(data)]
+    _timeout: Timeout,
+    _outer: OuterTestImplementation,
+}
+
+#[test]
+fn it_reports_all_missing_dependencies() {
+    let builder = InstanceBuilder::new();
+
+    let Err(BuilderError::Multiple(errors)) = builder.check::<ServiceWithManyDependencies>() else {
+        panic!("expected check to fail");
+    };
+
+    let missing = errors
+        .iter()
+        .map(|err| err.root().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        missing,
+        vec![
+            format!(
+                "data of type {} does not exist",
+                std::any::type_name::<Timeout>()
+            ),
+            format!(
+                "data of type {} does not exist",
+                std::any::type_name::<TestConfig>()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn it_declares_dependencies() {
+    let dependencies = OuterTestImplementation::dependencies();
+
+    let declared = dependencies
+        .iter()
+        .map(|d| (d.type_name(), d.kind()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        declared,
+        vec![
+            (
+                std::any::type_name::<InnerTestImplementation>(),
+                DependencyKind::Build
+            ),
+            (std::any::type_name::<Timeout>(), DependencyKind::Optional),
+        ]
+    );
+    assert_eq!(
+        dependencies[0].dependencies()[0].type_name(),
+        std::any::type_name::<TestConfig>()
+    );
+
+    let Err(BuilderError::Multiple(errors)) =
+        InstanceBuilder::new().check::<ServiceWithManyDependencies>()
+    else {
+        panic!("expected check to fail");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(builder().check::<OuterTestImplementation>().is_ok());
+}
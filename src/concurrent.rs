@@ -0,0 +1,372 @@
+use crate::{
+    BuilderError, CoerceFn, DataEntry, FromInstanceBuilder, InstanceBuilder, RegistrationError,
+    SharedInstance,
+};
+use std::any::{type_name, Any, TypeId};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Builder variant that allows registering data through a shared reference, e.g. by plugins
+/// loaded concurrently on different threads.
+///
+/// The builder wraps an [`InstanceBuilder`] in a read-write lock. Registrations take the write
+/// lock, lookups and builds the read lock, so builds run concurrently with each other but not
+/// with registrations. Anything not covered by the methods below, like scopes or named data, is
+/// available through [`ConcurrentInstanceBuilder::read`] and
+/// [`ConcurrentInstanceBuilder::write`].
+///
+/// # Consistency
+///
+/// Every operation is atomic: an insert is either fully visible to a lookup or not at all, and
+/// concurrent inserts of the same type are applied one after another, the last one wins. Data
+/// is handed out as `Arc`, so it stays valid when it gets replaced afterwards.
+///
+/// A build sees a consistent state of all data, registrations wait until running builds are
+/// finished. Consequently, constructors must not register through the concurrent builder, the
+/// write lock would wait for their own build. Instances of
+/// [`ConcurrentInstanceBuilder::build_shared`] are cached like by
+/// [`InstanceBuilder::build_shared`], they are not rebuilt when their data is replaced.
+///
+/// ```
+/// use ::instancebuilder::ConcurrentInstanceBuilder;
+///
+/// let builder = ConcurrentInstanceBuilder::new();
+///
+/// std::thread::scope(|s| {
+///     s.spawn(|| builder.insert(String::from("plugin a")));
+///     s.spawn(|| builder.insert(42usize));
+/// });
+///
+/// assert_eq!(*builder.data::<usize>().unwrap(), 42);
+/// ```
+#[derive(Default)]
+pub struct ConcurrentInstanceBuilder {
+    builder: RwLock<InstanceBuilder<'static>>,
+}
+
+impl ConcurrentInstanceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts data, returning the data previously registered for `D`.
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`InstanceBuilder::insert`] if `D` is already registered and the builder is
+    /// in strict mode.
+    pub fn insert<D: Any + Send + Sync>(&self, data: D) -> Option<Arc<D>> {
+        self.write(|builder| {
+            let previous = builder.replace(|builder| builder.data.remove(&TypeId::of::<D>()));
+            insert_shared(builder, data).unwrap_or_else(|err| panic!("{err}"));
+
+            previous.and_then(shared)
+        })
+    }
+
+    /// Inserts data unless data of type `D` is already registered, handing the data back
+    /// otherwise. The check and the insert happen atomically.
+    pub fn try_insert<D: Any + Send + Sync>(&self, data: D) -> Result<(), RegistrationError<D>> {
+        self.write(|builder| insert_shared(builder, data))
+    }
+
+    /// Registers a factory, see [`InstanceBuilder::register_factory`].
+    pub fn register_factory<D, F>(&self, factory: F)
+    where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        self.write(|builder| builder.register_factory(factory));
+    }
+
+    /// Inserts data under a name, see [`InstanceBuilder::insert_named`].
+    pub fn insert_named<D: Any + Send + Sync>(&self, name: impl Into<String>, data: D) {
+        self.write(|builder| builder.insert_named(name, data));
+    }
+
+    /// Binds the interface `I` to the implementation `T`, see [`InstanceBuilder::bind`].
+    pub fn bind<I, T>(&self, coerce: CoerceFn<T, I>)
+    where
+        I: ?Sized + Send + Sync + 'static,
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        self.write(|builder| builder.bind(coerce));
+    }
+
+    pub fn data<D: Any + Send + Sync>(&self) -> Result<Arc<D>, BuilderError> {
+        let registered = |builder: &InstanceBuilder| builder.data.contains_key(&TypeId::of::<D>());
+        let data = match self.read(lookup_shared::<D>)? {
+            Some(data) => Some(data),
+            // Data registered through `write` is not shared yet.
+            None if self.read(registered) => self.write(share::<D>)?,
+            None => None,
+        };
+
+        data.and_then(|data| data.downcast().ok())
+            .ok_or_else(|| BuilderError::DataDoesNotExist {
+                ty: type_name::<D>().to_string(),
+            })
+    }
+
+    /// Returns the data of type `D` if present, a failing factory is treated as missing data.
+    pub fn data_opt<D: Any + Send + Sync>(&self) -> Option<Arc<D>> {
+        self.data().ok()
+    }
+
+    /// Builds a new instance of `T`, see [`InstanceBuilder::build`].
+    pub fn build<T>(&self) -> Result<T, BuilderError>
+    where
+        T: FromInstanceBuilder + 'static,
+    {
+        self.read(InstanceBuilder::build)
+    }
+
+    /// Returns the shared instance of `T`, building it on first use, see
+    /// [`InstanceBuilder::build_shared`].
+    pub fn build_shared<T>(&self) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        self.read(InstanceBuilder::build_shared)
+    }
+
+    /// Returns the implementation bound to the interface `I`, see [`InstanceBuilder::resolve`].
+    pub fn resolve<I>(&self) -> Result<Arc<I>, BuilderError>
+    where
+        I: ?Sized + Send + Sync + 'static,
+    {
+        self.read(InstanceBuilder::resolve)
+    }
+
+    /// Calls `f` with the builder while holding the read lock.
+    pub fn read<R>(&self, f: impl FnOnce(&InstanceBuilder<'static>) -> R) -> R {
+        f(&self.builder.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Calls `f` with the builder while holding the write lock.
+    pub fn write<R>(&self, f: impl FnOnce(&mut InstanceBuilder<'static>) -> R) -> R {
+        f(&mut self.builder.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Returns the wrapped builder, e.g. to [`InstanceBuilder::freeze`] it once all plugins are
+    /// loaded.
+    pub fn into_inner(self) -> InstanceBuilder<'static> {
+        self.builder
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl From<InstanceBuilder<'static>> for ConcurrentInstanceBuilder {
+    fn from(builder: InstanceBuilder<'static>) -> Self {
+        Self {
+            builder: RwLock::new(builder),
+        }
+    }
+}
+
+fn insert_shared<D: Any + Send + Sync>(
+    builder: &mut InstanceBuilder<'static>,
+    data: D,
+) -> Result<(), RegistrationError<D>> {
+    let data =
+        InstanceBuilder::check_vacant::<D, _>(builder.data.contains_key(&TypeId::of::<D>()), data)?;
+
+    builder
+        .data
+        .insert(TypeId::of::<D>(), DataEntry::Shared(Arc::new(data)));
+
+    Ok(())
+}
+
+/// Returns the data of a removed entry, a factory returns its data only if it has been called
+/// already.
+fn shared<D: Any + Send + Sync>(entry: DataEntry) -> Option<Arc<D>> {
+    let data = match entry {
+        DataEntry::Value(data) => SharedInstance::from(data),
+        DataEntry::Shared(data) => data,
+        DataEntry::Factory { value, .. } => value.into_inner()?,
+    };
+
+    data.downcast().ok()
+}
+
+/// Returns the shared data of type `D`, calling its factory if necessary. Unshared data is
+/// treated as missing.
+fn lookup_shared<D: Any + Send + Sync>(
+    builder: &InstanceBuilder<'static>,
+) -> Result<Option<SharedInstance>, BuilderError> {
+    match builder.data.get(&TypeId::of::<D>()) {
+        Some(DataEntry::Shared(data)) => Ok(Some(data.clone())),
+        Some(DataEntry::Factory { value, .. }) => {
+            builder.lookup::<D>()?;
+            Ok(value.get().cloned())
+        }
+        Some(DataEntry::Value(_)) | None => Ok(None),
+    }
+}
+
+/// Turns the data of type `D` into shared data, returning it.
+fn share<D: Any + Send + Sync>(
+    builder: &mut InstanceBuilder<'static>,
+) -> Result<Option<SharedInstance>, BuilderError> {
+    if let Some(DataEntry::Value(_)) = builder.data.get(&TypeId::of::<D>()) {
+        if let Some(DataEntry::Value(data)) = builder.data.remove(&TypeId::of::<D>()) {
+            builder
+                .data
+                .insert(TypeId::of::<D>(), DataEntry::Shared(data.into()));
+        }
+    }
+
+    lookup_shared::<D>(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcurrentInstanceBuilder;
+    use crate::{BuilderError, FromInstanceBuilder, InstanceBuilder};
+    use std::sync::{Arc, Barrier};
+
+    // Stress tests, they provoke interleavings by repetition but can't enumerate them.
+    const THREADS: usize = 8;
+    const ITERATIONS: usize = 200;
+
+    struct Pair {
+        a: usize,
+        b: usize,
+    }
+
+    impl FromInstanceBuilder for Pair {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            let a = *builder.data::<usize>()?;
+            // Widen the window for concurrent inserts between both lookups.
+            std::thread::yield_now();
+            let b = *builder.data::<usize>()?;
+            Ok(Self { a, b })
+        }
+    }
+
+    #[test]
+    fn it_registers_concurrently() {
+        for _ in 0..ITERATIONS {
+            let builder = ConcurrentInstanceBuilder::new();
+            let barrier = Barrier::new(THREADS);
+
+            let inserted = std::thread::scope(|s| {
+                let handles = (0..THREADS)
+                    .map(|i| {
+                        let (builder, barrier) = (&builder, &barrier);
+                        s.spawn(move || {
+                            barrier.wait();
+                            let inserted = builder.try_insert(i).is_ok();
+                            builder.insert(i as u64);
+                            inserted
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(i, handle)| handle.join().unwrap().then_some(i))
+                    .collect::<Vec<_>>()
+            });
+
+            // exactly one try_insert succeeded, its value stays
+            assert_eq!(inserted.len(), 1);
+            assert_eq!(*builder.data::<usize>().unwrap(), inserted[0]);
+            assert!(*builder.data::<u64>().unwrap() < THREADS as u64);
+        }
+    }
+
+    #[test]
+    fn it_builds_from_a_consistent_state() {
+        let builder = ConcurrentInstanceBuilder::new();
+        builder.insert(0usize);
+        let barrier = Barrier::new(THREADS);
+
+        std::thread::scope(|s| {
+            for i in 0..THREADS {
+                let (builder, barrier) = (&builder, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    for j in 0..ITERATIONS {
+                        if i % 2 == 0 {
+                            builder.insert(i * ITERATIONS + j);
+                        } else {
+                            let pair = builder.build::<Pair>().unwrap();
+                            assert_eq!(pair.a, pair.b);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    struct Registry(String);
+
+    impl FromInstanceBuilder for Registry {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self(builder.data::<String>()?.clone()))
+        }
+    }
+
+    #[test]
+    fn it_shares_instances_across_threads() {
+        let builder = ConcurrentInstanceBuilder::new();
+        builder.register_factory(|_| Ok(String::from("plugins")));
+        let barrier = Barrier::new(THREADS);
+
+        let instances = std::thread::scope(|s| {
+            let handles = (0..THREADS)
+                .map(|_| {
+                    let (builder, barrier) = (&builder, &barrier);
+                    s.spawn(move || {
+                        barrier.wait();
+                        builder.build_shared::<Registry>().unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert!(instances.iter().all(|i| Arc::ptr_eq(i, &instances[0])));
+        assert_eq!(instances[0].0, "plugins");
+        assert_eq!(*builder.data::<String>().unwrap(), "plugins");
+    }
+
+    #[test]
+    fn it_keeps_replaced_data_alive() {
+        let builder = ConcurrentInstanceBuilder::new();
+        builder.insert(String::from("first"));
+
+        let first = builder.data::<String>().unwrap();
+        let previous = builder.insert(String::from("second")).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &previous));
+        assert_eq!(*first, "first");
+        assert_eq!(*builder.data::<String>().unwrap(), "second");
+    }
+
+    #[test]
+    fn it_rejects_modifying_shared_data() {
+        let builder = ConcurrentInstanceBuilder::new();
+        builder.write(|builder| builder.insert(String::from("unshared")));
+
+        let data = builder.data::<String>().unwrap();
+        assert!(matches!(
+            builder.write(|builder| builder.data_mut::<String>().map(|_| ())),
+            Err(BuilderError::DataShared { .. })
+        ));
+
+        drop(data);
+        builder.write(|builder| builder.data_mut::<String>().unwrap().push('!'));
+        assert_eq!(
+            builder.into_inner().remove::<String>().unwrap(),
+            "unshared!"
+        );
+    }
+}
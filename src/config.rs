@@ -0,0 +1,462 @@
+use crate::{BuilderError, ConfigError, InstanceBuilder};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{Map, Value};
+use std::any::{type_name, Any};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Separator of nested keys in environment variables, e.g. `APP_DATABASE__URL`.
+const ENV_NESTING: &str = "__";
+
+impl InstanceBuilder<'_> {
+    /// Deserializes `D` from a TOML, YAML or JSON file and inserts it.
+    ///
+    /// The format is derived from the file extension: `toml`, `yaml`, `yml` or `json`.
+    pub fn insert_config_from_file<D>(&mut self, path: impl AsRef<Path>) -> Result<(), BuilderError>
+    where
+        D: DeserializeOwned + Any + Send + Sync,
+    {
+        let config = from_file(path.as_ref()).map_err(config_error::<D>)?;
+        self.insert_checked::<D>(config)?;
+
+        Ok(())
+    }
+
+    /// Deserializes `D` from the environment variables starting with `prefix` and inserts it.
+    ///
+    /// The prefix is stripped and the remaining name is lower cased to get the field name, `__`
+    /// separates the fields of nested structs: with the prefix `APP_`, `APP_DATABASE__URL` sets
+    /// the field `url` of the field `database`. Values are parsed into the type of the field,
+    /// sequences are separated by commas.
+    pub fn insert_config_from_env<D>(&mut self, prefix: &str) -> Result<(), BuilderError>
+    where
+        D: DeserializeOwned + Any + Send + Sync,
+    {
+        let config = env(prefix)
+            .and_then(|value| {
+                deserialize(value).map_err(|err| ConfigError::new(env_origin(prefix), err))
+            })
+            .map_err(config_error::<D>)?;
+        self.insert_checked::<D>(config)?;
+
+        Ok(())
+    }
+}
+
+fn config_error<D>(source: ConfigError) -> BuilderError {
+    BuilderError::Config {
+        ty: type_name::<D>().to_string(),
+        source,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    pub(crate) fn of(path: &Path) -> Result<Self, ConfigError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            Some("json") => Ok(Format::Json),
+            _ => Err(ConfigError::new(
+                path.display().to_string(),
+                "unsupported file format, expected toml, yaml or json",
+            )),
+        }
+    }
+
+    /// Deserializes `content`, errors point to their location in `content` if possible.
+    pub(crate) fn parse<D: DeserializeOwned>(
+        self,
+        origin: &str,
+        content: &str,
+    ) -> Result<D, ConfigError> {
+        match self {
+            Format::Toml => toml::from_str(content).map_err(|err| {
+                let location = err.span().map(|span| location(content, span.start));
+                let err = ConfigError::new(origin, err.message().to_string());
+                match location {
+                    Some((line, column)) => err.at(line, column),
+                    None => err,
+                }
+            }),
+            Format::Yaml => serde_yaml::from_str(content).map_err(|err| {
+                let location = err.location();
+                let err = ConfigError::new(origin, err);
+                match location {
+                    Some(location) => err.at(location.line(), location.column()),
+                    None => err,
+                }
+            }),
+            Format::Json => serde_json::from_str(content).map_err(|err| {
+                let (line, column) = (err.line(), err.column());
+                ConfigError::new(origin, err).at(line, column)
+            }),
+        }
+    }
+}
+
+pub(crate) fn read(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|err| ConfigError::new(path.display().to_string(), err))
+}
+
+fn from_file<D: DeserializeOwned>(path: &Path) -> Result<D, ConfigError> {
+    Format::of(path)?.parse(&path.display().to_string(), &read(path)?)
+}
+
+/// Returns the line and column of the byte `offset`, both starting at 1.
+fn location(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |pos| pos + 1) + 1;
+
+    (line, column)
+}
+
+/// Collects the environment variables starting with `prefix`, see [`env_value`].
+pub(crate) fn env(prefix: &str) -> Result<Value, ConfigError> {
+    env_value(prefix, std::env::vars_os())
+}
+
+pub(crate) fn env_origin(prefix: &str) -> String {
+    format!("environment variables {prefix}*")
+}
+
+/// Collects the variables starting with `prefix` into an object of strings, nested by `__`.
+///
+/// Variables not starting with `prefix` are skipped, even if they are not valid unicode. A value
+/// of a matching variable that is not valid unicode is an error.
+pub(crate) fn env_value(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Value, ConfigError> {
+    let mut root = Map::new();
+
+    for (key, value) in vars {
+        let Some(key) = key.to_str().and_then(|key| key.strip_prefix(prefix)) else {
+            continue;
+        };
+        let value = value.into_string().map_err(|_| {
+            ConfigError::new(
+                env_origin(prefix),
+                format!("value of {prefix}{key} is not valid unicode"),
+            )
+        })?;
+        let key = key.to_lowercase();
+        let mut parts = key.split(ENV_NESTING).peekable();
+        let mut map = &mut root;
+
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                map.insert(part.to_string(), Value::String(value.clone()));
+                break;
+            }
+
+            let entry = map
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            map = entry.as_object_mut().expect("entry is an object");
+        }
+    }
+
+    Ok(Value::Object(root))
+}
+
+/// Deserializes `D` from `value`, parsing strings into the requested types.
+pub(crate) fn deserialize<D: DeserializeOwned>(value: Value) -> Result<D, serde_json::Error> {
+    D::deserialize(Lenient(value))
+}
+
+/// Deserializer of JSON values that accepts strings for booleans, numbers and sequences, as
+/// environment variables only provide strings.
+struct Lenient(Value);
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0 {
+                    Value::String(s) => visitor.$visit(s.trim().parse().map_err(|err| {
+                        serde_json::Error::custom(format!("invalid value {s:?}: {err}"))
+                    })?),
+                    value => value.$method(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(values) => {
+                visitor.visit_seq(SeqDeserializer::new(values.into_iter().map(Lenient)))
+            }
+            Value::Object(map) => visitor.visit_map(MapDeserializer::new(
+                map.into_iter().map(|(key, value)| (key, Lenient(value))),
+            )),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(Lenient(value)),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::String(s) => visitor.visit_seq(SeqDeserializer::new(
+                s.split(',')
+                    .filter(|item| !item.trim().is_empty())
+                    .map(|item| Lenient(Value::String(item.trim().to_string()))),
+            )),
+            value => Lenient(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::String(s) => visitor.visit_enum(s.into_deserializer()),
+            value => value.deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, serde_json::Error> for Lenient {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{deserialize, env_value};
+    use crate::{BuilderError, InstanceBuilder};
+    use serde::Deserialize;
+    use std::error::Error;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct DatabaseConfig {
+        url: String,
+        pool_size: u16,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AppConfig {
+        name: String,
+        debug: bool,
+        tags: Vec<String>,
+        timeout: Option<u64>,
+        database: DatabaseConfig,
+    }
+
+    /// Temporary directory of a test, removed with its files when dropped.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("instancebuilder-{}-{name}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        pub(crate) fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+
+        pub(crate) fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.path(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn expected() -> AppConfig {
+        AppConfig {
+            name: String::from("app"),
+            debug: true,
+            tags: vec![String::from("a"), String::from("b")],
+            timeout: None,
+            database: DatabaseConfig {
+                url: String::from("postgres://"),
+                pool_size: 8,
+            },
+        }
+    }
+
+    #[test]
+    fn it_loads_config_files() {
+        let dir = TempDir::new("files");
+        let toml = dir.write(
+            "app.toml",
+            "name = \"app\"\ndebug = true\ntags = [\"a\", \"b\"]\n\n[database]\nurl = \"postgres://\"\npool_size = 8\n",
+        );
+        let yaml = dir.write(
+            "app.yaml",
+            "name: app\ndebug: true\ntags: [a, b]\ndatabase:\n  url: postgres://\n  pool_size: 8\n",
+        );
+
+        for path in [toml, yaml] {
+            let mut builder = InstanceBuilder::new();
+            builder.insert_config_from_file::<AppConfig>(&path).unwrap();
+            assert_eq!(builder.data::<AppConfig>().unwrap(), &expected());
+        }
+    }
+
+    #[test]
+    fn it_rejects_registered_config_in_strict_mode() {
+        let dir = TempDir::new("strict");
+        let path = dir.write("strict.json", "8");
+
+        let mut builder = InstanceBuilder::strict();
+        builder.insert_config_from_file::<u32>(&path).unwrap();
+        let err = builder.insert_config_from_file::<u32>(&path).unwrap_err();
+
+        assert!(matches!(err, BuilderError::AlreadyRegistered { .. }));
+        assert_eq!(builder.data::<u32>().unwrap(), &8);
+    }
+
+    #[test]
+    fn it_reports_the_location_of_errors() {
+        let dir = TempDir::new("broken");
+        let path = dir.write(
+            "broken.toml",
+            "name = \"app\"\ndebug = true\ntags = []\n\n[database]\nurl = \"postgres://\"\npool_size = \"eight\"\n",
+        );
+
+        let err = InstanceBuilder::new()
+            .insert_config_from_file::<AppConfig>(&path)
+            .unwrap_err();
+
+        let BuilderError::Config { source, .. } = &err else {
+            panic!("expected config error, got {err}");
+        };
+        assert_eq!(source.origin(), path.display().to_string());
+        assert_eq!(source.line(), Some(7));
+        assert_eq!(source.column(), Some(13));
+    }
+
+    #[test]
+    fn it_deserializes_environment_variables() {
+        let vars = [
+            ("APP_NAME", "app"),
+            ("APP_DEBUG", "true"),
+            ("APP_TAGS", "a, b"),
+            ("APP_DATABASE__URL", "postgres://"),
+            ("APP_DATABASE__POOL_SIZE", "8"),
+            ("OTHER_NAME", "other"),
+        ]
+        .map(|(key, value)| (OsString::from(key), OsString::from(value)));
+
+        let config: AppConfig = deserialize(env_value("APP_", vars).unwrap()).unwrap();
+
+        assert_eq!(config, expected());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_rejects_environment_variables_that_are_not_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let invalid = || OsString::from_vec(vec![0xff, 0xfe]);
+        let vars = |key: &str| {
+            [
+                (OsString::from("APP_URL"), OsString::from("postgres://")),
+                (OsString::from("UNRELATED_BIN"), invalid()),
+                (invalid(), OsString::from("unrelated")),
+                (OsString::from(key), invalid()),
+            ]
+        };
+
+        // variables of other prefixes are skipped
+        let value = env_value("APP_", vars("OTHER_BIN")).unwrap();
+        assert_eq!(value["url"], "postgres://");
+
+        let err = env_value("APP_", vars("APP_BIN")).unwrap_err();
+        assert_eq!(err.origin(), "environment variables APP_*");
+        assert_eq!(err.to_string(), "environment variables APP_*");
+        assert!(err
+            .source()
+            .unwrap()
+            .to_string()
+            .contains("APP_BIN is not valid unicode"));
+    }
+
+    #[test]
+    fn it_inserts_config_from_the_environment() {
+        std::env::set_var("INSTANCEBUILDER_TEST_URL", "postgres://");
+        std::env::set_var("INSTANCEBUILDER_TEST_POOL_SIZE", "many");
+
+        let mut builder = InstanceBuilder::new();
+        let err = builder
+            .insert_config_from_env::<DatabaseConfig>("INSTANCEBUILDER_TEST_")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("environment variables INSTANCEBUILDER_TEST_*"));
+
+        std::env::set_var("INSTANCEBUILDER_TEST_POOL_SIZE", "8");
+        builder
+            .insert_config_from_env::<DatabaseConfig>("INSTANCEBUILDER_TEST_")
+            .unwrap();
+        assert_eq!(builder.data::<DatabaseConfig>().unwrap().pool_size, 8);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::InstanceBuilder;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Read-only, cheaply cloneable [`InstanceBuilder`], created by [`InstanceBuilder::freeze`].
+///
+/// All clones share the same data and cached instances. The builder API is available through
+/// `Deref`, registering data is not possible anymore.
+///
+/// ```
+/// use ::instancebuilder::InstanceBuilder;
+///
+/// let mut builder = InstanceBuilder::new();
+/// builder.insert(String::from("help me!"));
+///
+/// let container = builder.freeze();
+/// let shared = container.clone();
+///
+/// std::thread::spawn(move || {
+///     assert_eq!(shared.data::<String>().unwrap(), "help me!");
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Container {
+    builder: Arc<InstanceBuilder<'static>>,
+}
+
+impl InstanceBuilder<'static> {
+    /// Turns the builder into a [`Container`] that can be shared across threads.
+    pub fn freeze(self) -> Container {
+        Container {
+            builder: Arc::new(self),
+        }
+    }
+}
+
+impl From<InstanceBuilder<'static>> for Container {
+    fn from(builder: InstanceBuilder<'static>) -> Self {
+        builder.freeze()
+    }
+}
+
+impl Deref for Container {
+    type Target = InstanceBuilder<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.builder
+    }
+}
+
+impl AsRef<InstanceBuilder<'static>> for Container {
+    fn as_ref(&self) -> &InstanceBuilder<'static> {
+        &self.builder
+    }
+}
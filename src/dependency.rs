@@ -0,0 +1,61 @@
+use crate::FromInstanceBuilder;
+use std::any::{type_name, Any, TypeId};
+
+/// Dependency of a type on the builder, declared by [`FromInstanceBuilder::dependencies`].
+#[derive(Clone, Copy, Debug)]
+pub struct Dependency {
+    type_id: TypeId,
+    type_name: &'static str,
+    kind: DependencyKind,
+    dependencies: fn() -> Vec<Dependency>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DependencyKind {
+    /// Required data, looked up with `InstanceBuilder::data`.
+    Data,
+    /// Optional data, looked up with `InstanceBuilder::data_opt`.
+    Optional,
+    /// Nested instance, built with `InstanceBuilder::build`.
+    Build,
+}
+
+impl Dependency {
+    pub fn data<D: Any>() -> Self {
+        Self::new::<D>(DependencyKind::Data, Vec::new)
+    }
+
+    pub fn optional<D: Any>() -> Self {
+        Self::new::<D>(DependencyKind::Optional, Vec::new)
+    }
+
+    pub fn build<T: FromInstanceBuilder + 'static>() -> Self {
+        Self::new::<T>(DependencyKind::Build, T::dependencies)
+    }
+
+    fn new<D: Any>(kind: DependencyKind, dependencies: fn() -> Vec<Dependency>) -> Self {
+        Self {
+            type_id: TypeId::of::<D>(),
+            type_name: type_name::<D>(),
+            kind,
+            dependencies,
+        }
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn kind(&self) -> DependencyKind {
+        self.kind
+    }
+
+    /// Returns the declared dependencies of a [`DependencyKind::Build`] dependency.
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        (self.dependencies)()
+    }
+}
@@ -0,0 +1,324 @@
+use std::any::type_name;
+use std::error::Error;
+use std::fmt::Formatter;
+
+#[derive(Debug)]
+pub enum BuilderError {
+    DataDoesNotExist {
+        ty: String,
+    },
+    NamedDataDoesNotExist {
+        ty: String,
+        name: String,
+    },
+    AlreadyRegistered {
+        ty: String,
+    },
+    AlreadyTaken {
+        ty: String,
+    },
+    /// The data of type `ty` can't be modified while it is held elsewhere, see
+    /// [`ConcurrentInstanceBuilder::data`](crate::ConcurrentInstanceBuilder::data).
+    DataShared {
+        ty: String,
+    },
+    CyclicDependency {
+        path: Vec<String>,
+    },
+    /// Constructing an instance of `ty` failed with an error of the implementation.
+    Construction {
+        ty: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// Building the types in `path`, outermost first, failed with `source`.
+    Resolution {
+        path: Vec<String>,
+        source: Box<BuilderError>,
+    },
+    /// Loading the configuration `ty` failed.
+    Config {
+        ty: String,
+        source: ConfigError,
+    },
+    /// Installing the module `name` failed.
+    Module {
+        name: String,
+        source: Box<BuilderError>,
+    },
+    /// Several dependencies failed, e.g. multiple fields of a derived implementation.
+    Multiple(Vec<BuilderError>),
+    Other(String),
+}
+
+impl BuilderError {
+    /// Wraps an error that occurred while constructing an instance of `T`.
+    pub fn construction<T: ?Sized>(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        BuilderError::Construction {
+            ty: type_name::<T>().to_string(),
+            source: source.into(),
+        }
+    }
+
+    /// Combines the errors, a single error is returned as is.
+    pub fn from_errors(errors: impl IntoIterator<Item = BuilderError>) -> Self {
+        let mut errors = errors.into_iter().collect::<Vec<_>>();
+
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => BuilderError::Multiple(errors),
+        }
+    }
+
+    /// Splits the error into the errors that caused it, each wrapped in its full resolution path.
+    pub fn into_leaves(self) -> Vec<BuilderError> {
+        match self {
+            BuilderError::Multiple(errors) => errors
+                .into_iter()
+                .flat_map(BuilderError::into_leaves)
+                .collect(),
+            BuilderError::Resolution { path, source } => source
+                .into_leaves()
+                .into_iter()
+                .map(|leaf| match leaf {
+                    BuilderError::Resolution {
+                        path: inner,
+                        source,
+                    } => BuilderError::Resolution {
+                        path: path.iter().cloned().chain(inner).collect(),
+                        source,
+                    },
+                    leaf => BuilderError::Resolution {
+                        path: path.clone(),
+                        source: Box::new(leaf),
+                    },
+                })
+                .collect(),
+            err => vec![err],
+        }
+    }
+
+    /// Returns the error that caused the resolution to fail, without the resolution path.
+    pub fn root(&self) -> &BuilderError {
+        match self {
+            BuilderError::Resolution { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// Records that the error occurred while building `T`.
+    pub(crate) fn resolving<T: ?Sized>(self) -> Self {
+        match self {
+            // The cycle already names every type involved.
+            BuilderError::CyclicDependency { .. } => self,
+            BuilderError::Resolution { mut path, source } => {
+                path.insert(0, type_name::<T>().to_string());
+                BuilderError::Resolution { path, source }
+            }
+            err => BuilderError::Resolution {
+                path: vec![type_name::<T>().to_string()],
+                source: Box::new(err),
+            },
+        }
+    }
+}
+
+impl Error for BuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuilderError::Construction { source, .. } => Some(source.as_ref()),
+            // The location of the config error is part of the message.
+            BuilderError::Config { source, .. } => source.source(),
+            // The message of the wrapped error is part of the message of the resolution.
+            BuilderError::Resolution { source, .. } => source.source(),
+            BuilderError::Module { source, .. } => source.source(),
+            _ => None,
+        }
+    }
+}
+
+impl ::std::fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuilderError::DataDoesNotExist { ty } => write!(f, "data of type {ty} does not exist"),
+            BuilderError::NamedDataDoesNotExist { ty, name } => {
+                write!(f, "data of type {ty} named {name:?} does not exist")
+            }
+            BuilderError::AlreadyRegistered { ty } => {
+                write!(f, "data of type {ty} is already registered")
+            }
+            BuilderError::AlreadyTaken { ty } => {
+                write!(f, "data of type {ty} has already been taken")
+            }
+            BuilderError::DataShared { ty } => {
+                write!(f, "data of type {ty} is shared and can't be modified")
+            }
+            BuilderError::CyclicDependency { path } => {
+                write!(f, "cyclic dependency: {}", path.join(" -> "))
+            }
+            BuilderError::Construction { ty, .. } => write!(f, "failed to construct {ty}"),
+            BuilderError::Config { ty, source } => {
+                write!(f, "failed to load configuration {ty} from {source}")
+            }
+            BuilderError::Resolution { path, source } => {
+                write!(f, "failed to build {}: {source}", path.join(" -> "))
+            }
+            BuilderError::Module { name, source } => {
+                write!(f, "failed to install module {name}: {source}")
+            }
+            BuilderError::Multiple(errors) => {
+                write!(f, "{} errors occurred", errors.len())?;
+                for err in errors {
+                    write!(f, "; {err}")?;
+                }
+                Ok(())
+            }
+            BuilderError::Other(err) => {
+                write!(f, "other error: {err}")
+            }
+        }
+    }
+}
+
+/// Error of the `try_` registration methods like [`InstanceBuilder::try_insert`], handing back
+/// the value that was not registered, like `HashMap::try_insert`.
+///
+/// [`InstanceBuilder::try_insert`]: crate::InstanceBuilder::try_insert
+pub struct RegistrationError<V> {
+    ty: &'static str,
+    value: V,
+}
+
+impl<V> RegistrationError<V> {
+    pub(crate) fn new<D: ?Sized>(value: V) -> Self {
+        Self {
+            ty: type_name::<D>(),
+            value,
+        }
+    }
+
+    /// Returns the rejected value.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// Returns the rejected value, dropping the error.
+    pub fn into_value(self) -> V {
+        self.value
+    }
+}
+
+impl<V> From<RegistrationError<V>> for BuilderError {
+    fn from(err: RegistrationError<V>) -> Self {
+        BuilderError::AlreadyRegistered {
+            ty: err.ty.to_string(),
+        }
+    }
+}
+
+// Implemented by hand, the rejected value does not need to implement `Debug`.
+impl<V> ::std::fmt::Debug for RegistrationError<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegistrationError")
+            .field("ty", &self.ty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<V> ::std::fmt::Display for RegistrationError<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "data of type {} is already registered", self.ty)
+    }
+}
+
+impl<V> Error for RegistrationError<V> {}
+
+/// Converts errors of implementations into [`BuilderError::Construction`], so they can be
+/// propagated with `?` inside of `try_from_builder`.
+///
+/// ```
+/// use ::instancebuilder::{BuilderError, InstanceBuilder, FromInstanceBuilder, ResultExt};
+///
+/// struct Port(u16);
+///
+/// impl FromInstanceBuilder for Port {
+///     fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+///         let port: &String = builder.data()?;
+///         Ok(Self(port.parse().construction::<Self>()?))
+///     }
+/// }
+/// ```
+pub trait ResultExt<T> {
+    /// Maps the error into a [`BuilderError::Construction`] of `C`.
+    fn construction<C: ?Sized>(self) -> Result<T, BuilderError>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Error + Send + Sync + 'static,
+{
+    fn construction<C: ?Sized>(self) -> Result<T, BuilderError> {
+        self.map_err(BuilderError::construction::<C>)
+    }
+}
+
+/// Error of loading a configuration, pointing to the location of the error if known.
+#[derive(Debug)]
+pub struct ConfigError {
+    origin: String,
+    line: Option<usize>,
+    column: Option<usize>,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+// Only constructed with the `config` feature, the type is always available so that matching
+// on `BuilderError` does not depend on the enabled features.
+#[cfg_attr(not(feature = "config"), allow(dead_code))]
+impl ConfigError {
+    pub(crate) fn new(
+        origin: impl Into<String>,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            origin: origin.into(),
+            line: None,
+            column: None,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    /// Source of the configuration, e.g. the path of the file.
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Line of the error, starting at 1.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Column of the error, starting at 1.
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl ::std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{line}:{column}", self.origin),
+            _ => write!(f, "{}", self.origin),
+        }
+    }
+}
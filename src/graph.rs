@@ -0,0 +1,436 @@
+use crate::observer::{self, BuildObserver};
+use crate::{BuilderError, Dependency, DependencyKind, FromInstanceBuilder, InstanceBuilder};
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Dependency graph of a type, created by [`InstanceBuilder::graph`] or
+/// [`InstanceBuilder::declared_graph`].
+#[derive(Clone, Debug, Default)]
+pub struct DependencyGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// Type name of the node.
+    pub name: &'static str,
+    /// Whether the node is required data that is not registered in the builder.
+    pub missing: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Edge {
+    /// Index of the dependent node.
+    pub from: usize,
+    /// Index of the dependency node.
+    pub to: usize,
+    pub kind: DependencyKind,
+}
+
+impl DependencyGraph {
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Renders the graph in the Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            let color = if node.missing { ", color=red" } else { "" };
+            let _ = writeln!(dot, "    n{id} [label=\"{}\"{color}];", escape(node.name));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                DependencyKind::Build => "solid",
+                DependencyKind::Data => "dashed",
+                DependencyKind::Optional => "dotted",
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} -> n{} [label=\"{}\", style={style}];",
+                edge.from,
+                edge.to,
+                kind_name(edge.kind)
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as JSON object with a list of `nodes` and a list of `edges` referring
+    /// to the nodes by their index.
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"name\":\"{}\",\"missing\":{}}}",
+                    escape(node.name),
+                    node.missing
+                )
+            })
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                format!(
+                    "{{\"from\":{},\"to\":{},\"kind\":\"{}\"}}",
+                    edge.from,
+                    edge.to,
+                    kind_name(edge.kind)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}]}}",
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+impl InstanceBuilder<'_> {
+    /// Creates the dependency graph of `T` by building it, recording the builds and data
+    /// lookups of each build as its edges.
+    ///
+    /// `T` is built in a throwaway scope like [`InstanceBuilder::build`] and dropped, see
+    /// [`InstanceBuilder::build_shared`] for instances that are cached beyond it. A cached
+    /// instance is recorded without its dependencies, as it isn't built again. A failing build
+    /// ends the graph at the failure, missing data is marked as such.
+    ///
+    /// Use [`InstanceBuilder::declared_graph`] to create the graph without building anything.
+    pub fn graph<T>(&self) -> DependencyGraph
+    where
+        T: FromInstanceBuilder + 'static,
+    {
+        let recorder = Arc::new(Recorder::default());
+        let scope = self.create_scope();
+
+        observer::observe(recorder.clone(), || scope.build::<T>().map(drop).ok());
+
+        let mut recording = recorder.recording();
+        std::mem::take(&mut recording.graph)
+    }
+
+    /// Creates the dependency graph of `T` from the declared
+    /// [`FromInstanceBuilder::dependencies`], without building anything.
+    pub fn declared_graph<T>(&self) -> DependencyGraph
+    where
+        T: FromInstanceBuilder + 'static,
+    {
+        let mut graph = DependencyGraph::default();
+        let mut ids = HashMap::new();
+
+        graph.nodes.push(Node {
+            name: type_name::<T>(),
+            missing: false,
+        });
+        ids.insert(TypeId::of::<T>(), 0);
+
+        self.add_to_graph(0, T::dependencies(), &mut graph, &mut ids);
+
+        graph
+    }
+
+    fn add_to_graph(
+        &self,
+        from: usize,
+        dependencies: Vec<Dependency>,
+        graph: &mut DependencyGraph,
+        ids: &mut HashMap<TypeId, usize>,
+    ) {
+        for dependency in dependencies {
+            let missing =
+                dependency.kind() == DependencyKind::Data && !self.contains(dependency.type_id());
+
+            let to = match ids.get(&dependency.type_id()) {
+                Some(id) => {
+                    // Seen as optional before, required data may still be missing.
+                    graph.nodes[*id].missing |= missing;
+                    *id
+                }
+                None => {
+                    let id = graph.nodes.len();
+                    graph.nodes.push(Node {
+                        name: dependency.type_name(),
+                        missing,
+                    });
+                    ids.insert(dependency.type_id(), id);
+
+                    if dependency.kind() == DependencyKind::Build {
+                        self.add_to_graph(id, dependency.dependencies(), graph, ids);
+                    }
+                    id
+                }
+            };
+
+            graph.edges.push(Edge {
+                from,
+                to,
+                kind: dependency.kind(),
+            });
+        }
+    }
+}
+
+/// Records the builds and lookups on the thread creating a graph.
+#[derive(Default)]
+struct Recorder {
+    state: Mutex<Recording>,
+}
+
+#[derive(Default)]
+struct Recording {
+    graph: DependencyGraph,
+    ids: HashMap<&'static str, usize>,
+    /// Nodes being built, innermost last.
+    stack: Vec<usize>,
+}
+
+impl Recording {
+    /// Adds an edge from the current build to `ty`, returning the node of `ty`.
+    fn record(&mut self, ty: &'static str, kind: DependencyKind, missing: bool) -> usize {
+        let graph = &mut self.graph;
+        let to = *self.ids.entry(ty).or_insert_with(|| {
+            graph.nodes.push(Node { name: ty, missing });
+            graph.nodes.len() - 1
+        });
+        // Seen as optional before, required data may still be missing.
+        graph.nodes[to].missing |= missing;
+
+        if let Some(&from) = self.stack.last() {
+            let recorded = graph
+                .edges
+                .iter()
+                .any(|edge| edge.from == from && edge.to == to && edge.kind == kind);
+            if !recorded {
+                graph.edges.push(Edge { from, to, kind });
+            }
+        }
+
+        to
+    }
+}
+
+impl Recorder {
+    fn recording(&self) -> MutexGuard<'_, Recording> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl BuildObserver for Recorder {
+    fn build_started(&self, ty: &'static str) {
+        let mut recording = self.recording();
+        let id = recording.record(ty, DependencyKind::Build, false);
+        recording.stack.push(id);
+    }
+
+    fn build_finished(&self, _ty: &'static str, _elapsed: Duration, _: Result<(), &BuilderError>) {
+        self.recording().stack.pop();
+    }
+
+    fn cache_hit(&self, ty: &'static str) {
+        self.recording().record(ty, DependencyKind::Build, false);
+    }
+
+    fn data_lookup(&self, ty: &'static str, kind: DependencyKind, found: bool) {
+        let missing = kind == DependencyKind::Data && !found;
+        self.recording().record(ty, kind, missing);
+    }
+}
+
+fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Data => "data",
+        DependencyKind::Optional => "optional",
+        DependencyKind::Build => "build",
+    }
+}
+
+/// Escapes a string for DOT and JSON string literals.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BuilderError, Dependency, DependencyKind, FromInstanceBuilder, InstanceBuilder};
+    use std::any::type_name;
+
+    struct Service;
+    struct Repository;
+
+    impl FromInstanceBuilder for Service {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self)
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![
+                Dependency::build::<Repository>(),
+                Dependency::optional::<u8>(),
+                Dependency::data::<String>(),
+            ]
+        }
+    }
+
+    impl FromInstanceBuilder for Repository {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self)
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![Dependency::data::<String>(), Dependency::build::<Service>()]
+        }
+    }
+
+    #[test]
+    fn it_exports_the_dependency_graph() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(String::from("postgres://"));
+
+        let graph = builder.declared_graph::<Service>();
+
+        assert_eq!(graph.nodes().len(), 4);
+        assert_eq!(graph.edges().len(), 5);
+        assert!(graph.nodes().iter().all(|node| !node.missing));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph dependencies {\n"));
+        assert!(dot.contains("n1 -> n0 [label=\"build\", style=solid];"));
+
+        let json = graph.to_json();
+        assert!(json.contains("{\"name\":\"u8\",\"missing\":false}"));
+        assert!(json.contains("{\"from\":0,\"to\":2,\"kind\":\"data\"}"));
+    }
+
+    struct Gateway;
+
+    impl FromInstanceBuilder for Gateway {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self)
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![
+                Dependency::optional::<String>(),
+                Dependency::build::<Repository>(),
+            ]
+        }
+    }
+
+    #[test]
+    fn it_marks_missing_data() {
+        let graph = InstanceBuilder::new().declared_graph::<Service>();
+
+        let missing = graph
+            .nodes()
+            .iter()
+            .filter(|node| node.missing)
+            .map(|node| node.name)
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec![std::any::type_name::<String>()]);
+        assert!(graph.to_dot().contains("color=red"));
+    }
+
+    struct Handler;
+    struct Session;
+
+    // Hand written without declared dependencies, the graph is recorded from the build.
+    impl FromInstanceBuilder for Handler {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            builder.data_opt::<u8>();
+            builder.build_shared::<Session>()?;
+            builder.build_shared::<Session>()?;
+            Ok(Self)
+        }
+    }
+
+    impl FromInstanceBuilder for Session {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            builder.data::<String>()?;
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn it_records_the_dependency_graph_of_a_build() {
+        let mut builder = InstanceBuilder::new();
+
+        let graph = builder.graph::<Handler>();
+
+        let names = graph
+            .nodes()
+            .iter()
+            .map(|node| (node.name, node.missing))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (type_name::<Handler>(), false),
+                ("u8", false),
+                (type_name::<Session>(), false),
+                (type_name::<String>(), true),
+            ]
+        );
+        let edges = graph
+            .edges()
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, DependencyKind::Optional),
+                (0, 2, DependencyKind::Build),
+                (2, 3, DependencyKind::Data),
+            ]
+        );
+        assert!(builder.declared_graph::<Handler>().edges().is_empty());
+
+        // the second build of the session is a cache hit, recorded by the same edge
+        builder.insert(String::from("cookie"));
+        let graph = builder.graph::<Handler>();
+        assert_eq!(graph.edges().len(), 3);
+        assert!(graph.nodes().iter().all(|node| !node.missing));
+    }
+
+    #[test]
+    fn it_marks_data_missing_after_optional_lookups() {
+        let builder = InstanceBuilder::new();
+        let graph = builder.declared_graph::<Gateway>();
+
+        let string = graph
+            .nodes()
+            .iter()
+            .find(|node| node.name == std::any::type_name::<String>())
+            .unwrap();
+        assert!(string.missing);
+        assert!(builder.check::<Gateway>().is_err());
+    }
+}
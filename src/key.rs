@@ -0,0 +1,52 @@
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+
+/// Typed name of a data registration, avoids repeating the name and type of named data.
+///
+/// ```
+/// use ::instancebuilder::{InstanceBuilder, Key};
+///
+/// struct Pool {
+///     url: String,
+/// }
+///
+/// const PRIMARY: Key<Pool> = Key::new("primary");
+/// const REPLICA: Key<Pool> = Key::new("replica");
+///
+/// let mut builder = InstanceBuilder::new();
+/// builder.insert_key(&PRIMARY, Pool { url: String::from("postgres://primary") });
+/// builder.insert_key(&REPLICA, Pool { url: String::from("postgres://replica") });
+///
+/// assert_eq!(builder.data_key(&REPLICA).unwrap().url, "postgres://replica");
+/// ```
+pub struct Key<D> {
+    name: &'static str,
+    _data: PhantomData<fn() -> D>,
+}
+
+impl<D> Key<D> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _data: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<D> Clone for Key<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D> Copy for Key<D> {}
+
+impl<D> Debug for Key<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Key").field(&self.name).finish()
+    }
+}
@@ -0,0 +1,326 @@
+use crate::config::{deserialize, env, read, Format};
+use crate::{BuilderError, ConfigError, InstanceBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::any::{type_name, Any};
+use std::path::{Path, PathBuf};
+
+/// Replacement of redacted values in [`ConfigLayers::effective`].
+const REDACTED: &str = "<redacted>";
+
+/// Keys containing one of these words are redacted by default.
+const SECRETS: [&str; 5] = ["password", "secret", "token", "credential", "private_key"];
+
+/// Configuration merged from several layers, e.g. a base file, a profile file and environment
+/// variables.
+///
+/// Layers are merged in the order they are added, later layers take precedence: tables are
+/// merged key by key, any other value replaces the value of the previous layers.
+///
+/// ```no_run
+/// # use instancebuilder::{ConfigLayers, InstanceBuilder};
+/// # #[derive(serde::Deserialize)]
+/// # struct AppConfig;
+/// let layers = ConfigLayers::new()
+///     .profile("config/app.toml", "prod")
+///     .env("APP_");
+///
+/// println!("{}", layers.effective().unwrap());
+///
+/// let mut builder = InstanceBuilder::new();
+/// builder.insert_config_layers::<AppConfig>(&layers).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigLayers {
+    layers: Vec<Layer>,
+    redacted: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Layer {
+    File { path: PathBuf, required: bool },
+    Env { prefix: String },
+}
+
+impl ConfigLayers {
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            redacted: SECRETS.iter().map(|secret| secret.to_string()).collect(),
+        }
+    }
+
+    /// Adds a TOML, YAML or JSON file, merging fails if it does not exist.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.into(),
+            required: true,
+        });
+        self
+    }
+
+    /// Adds a TOML, YAML or JSON file which is skipped if it does not exist.
+    pub fn optional_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.into(),
+            required: false,
+        });
+        self
+    }
+
+    /// Adds the file `base` and the optional profile file next to it, named after the profile:
+    /// `config/app.toml` with the profile `prod` adds `config/app.toml` and `config/app.prod.toml`.
+    pub fn profile(self, base: impl Into<PathBuf>, profile: &str) -> Self {
+        let base = base.into();
+        let profile_path = profile_path(&base, profile);
+
+        self.file(base).optional_file(profile_path)
+    }
+
+    /// Adds the environment variables starting with `prefix`, see
+    /// [`InstanceBuilder::insert_config_from_env`] for the naming of the variables.
+    pub fn env(mut self, prefix: impl Into<String>) -> Self {
+        self.layers.push(Layer::Env {
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    /// Redacts the values of keys containing `word` in [`ConfigLayers::effective`], in addition
+    /// to keys containing `password`, `secret`, `token`, `credential` or `private_key`.
+    pub fn redact(mut self, word: impl Into<String>) -> Self {
+        self.redacted.push(word.into().to_lowercase());
+        self
+    }
+
+    /// Merges all layers into a single value.
+    pub fn merge(&self) -> Result<Value, ConfigError> {
+        let mut merged = Value::Object(Map::new());
+
+        for layer in &self.layers {
+            let value = match layer {
+                Layer::File { path, required } => {
+                    if !*required && !path.exists() {
+                        continue;
+                    }
+                    Format::of(path)?.parse(&path.display().to_string(), &read(path)?)?
+                }
+                Layer::Env { prefix } => env(prefix)?,
+            };
+
+            merge(&mut merged, value);
+        }
+
+        Ok(merged)
+    }
+
+    /// Merges all layers and deserializes `D` from the result.
+    pub fn load<D: DeserializeOwned>(&self) -> Result<D, ConfigError> {
+        deserialize(self.merge()?).map_err(|err| ConfigError::new(self.origin(), err))
+    }
+
+    /// Returns the merged configuration as pretty printed JSON with secrets redacted.
+    pub fn effective(&self) -> Result<String, ConfigError> {
+        let mut merged = self.merge()?;
+        redact(&mut merged, &self.redacted);
+
+        serde_json::to_string_pretty(&merged).map_err(|err| ConfigError::new(self.origin(), err))
+    }
+
+    fn origin(&self) -> String {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| match layer {
+                Layer::File { path, .. } => path.display().to_string(),
+                Layer::Env { prefix } => format!("{prefix}*"),
+            })
+            .collect::<Vec<_>>();
+
+        format!("configuration layers [{}]", layers.join(", "))
+    }
+}
+
+impl Default for ConfigLayers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstanceBuilder<'_> {
+    /// Deserializes `D` from the merged `layers` and inserts it.
+    pub fn insert_config_layers<D>(&mut self, layers: &ConfigLayers) -> Result<(), BuilderError>
+    where
+        D: DeserializeOwned + Any + Send + Sync,
+    {
+        let config = layers.load::<D>().map_err(|source| BuilderError::Config {
+            ty: type_name::<D>().to_string(),
+            source,
+        })?;
+        self.insert_checked::<D>(config)?;
+
+        Ok(())
+    }
+}
+
+fn profile_path(base: &Path, profile: &str) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(ext) => format!("{stem}.{profile}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{profile}"),
+    };
+
+    base.with_file_name(name)
+}
+
+/// Merges `value` into `target`, tables are merged recursively and other values are replaced.
+fn merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(map)) => {
+            for (key, value) in map {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+fn redact(value: &mut Value, redacted: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if redacted.iter().any(|word| key.contains(word.as_str())) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value, redacted);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| redact(value, redacted)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigLayers;
+    use crate::config::tests::TempDir;
+    use crate::InstanceBuilder;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct DatabaseConfig {
+        url: String,
+        password: String,
+        pool_size: u16,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AppConfig {
+        name: String,
+        debug: bool,
+        database: DatabaseConfig,
+    }
+
+    /// Returns the layers of files written to `dir` and the environment variables of `prefix`.
+    fn layers(dir: &TempDir, prefix: &str) -> ConfigLayers {
+        let base = dir.write(
+            "app.toml",
+            "name = \"app\"\ndebug = true\n\n[database]\nurl = \"postgres://localhost\"\npassword = \"dev\"\npool_size = 4\n",
+        );
+        let profile = dir.write(
+            "app.prod.yaml",
+            "debug: false\ndatabase:\n  url: postgres://prod\n",
+        );
+
+        ConfigLayers::new()
+            .file(base)
+            .optional_file(profile)
+            .optional_file(dir.path("missing.json"))
+            .env(prefix)
+    }
+
+    #[test]
+    fn it_prefers_later_layers() {
+        std::env::set_var("INSTANCEBUILDER_LAYERS_DATABASE__POOL_SIZE", "16");
+        std::env::set_var("INSTANCEBUILDER_LAYERS_DATABASE__PASSWORD", "hunter2");
+
+        let dir = TempDir::new("precedence");
+        let mut builder = InstanceBuilder::new();
+        builder
+            .insert_config_layers::<AppConfig>(&layers(&dir, "INSTANCEBUILDER_LAYERS_"))
+            .unwrap();
+
+        assert_eq!(
+            builder.data::<AppConfig>().unwrap(),
+            &AppConfig {
+                name: String::from("app"),
+                debug: false,
+                database: DatabaseConfig {
+                    url: String::from("postgres://prod"),
+                    password: String::from("hunter2"),
+                    pool_size: 16,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn it_redacts_secrets_of_the_effective_config() {
+        let dir = TempDir::new("effective");
+        let effective = layers(&dir, "INSTANCEBUILDER_EFFECTIVE_")
+            .redact("URL")
+            .effective()
+            .unwrap();
+
+        assert!(effective.contains("\"name\": \"app\""));
+        assert!(effective.contains("\"password\": \"<redacted>\""));
+        assert!(effective.contains("\"url\": \"<redacted>\""));
+        assert!(!effective.contains("dev"));
+        assert!(!effective.contains("postgres"));
+    }
+
+    #[test]
+    fn it_skips_missing_profile_files() {
+        let dir = TempDir::new("profile");
+        let base = dir.write("app.toml", "name = \"app\"\n");
+        dir.write("app.test.toml", "name = \"test\"\n");
+
+        let merged = ConfigLayers::new().profile(&base, "test").merge().unwrap();
+        assert_eq!(merged["name"], "test");
+
+        let merged = ConfigLayers::new().profile(&base, "prod").merge().unwrap();
+        assert_eq!(merged["name"], "app");
+
+        assert!(ConfigLayers::new()
+            .file(dir.path("missing.toml"))
+            .merge()
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_reports_environment_variables_that_are_not_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        std::env::set_var(
+            "INSTANCEBUILDER_UNICODE_BIN",
+            std::ffi::OsString::from_vec(vec![0xff, 0xfe]),
+        );
+
+        assert!(ConfigLayers::new()
+            .env("INSTANCEBUILDER_UNRELATED_")
+            .merge()
+            .is_ok());
+        assert!(ConfigLayers::new()
+            .env("INSTANCEBUILDER_UNICODE_")
+            .effective()
+            .is_err());
+    }
+}
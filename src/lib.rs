@@ -1,6 +1,53 @@
 use std::any::{type_name, Any, TypeId};
-use std::collections::HashMap;
-use std::fmt::Formatter;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+
+mod concurrent;
+#[cfg(feature = "config")]
+mod config;
+mod container;
+mod dependency;
+mod error;
+#[cfg(feature = "graph")]
+mod graph;
+mod key;
+#[cfg(feature = "config")]
+mod layers;
+mod lifecycle;
+mod local;
+mod module;
+mod observer;
+mod overrides;
+mod slot;
+mod stack;
+#[cfg(feature = "testing")]
+mod testing;
+
+pub use concurrent::ConcurrentInstanceBuilder;
+pub use container::Container;
+pub use dependency::{Dependency, DependencyKind};
+pub use error::{BuilderError, ConfigError, RegistrationError, ResultExt};
+#[cfg(feature = "graph")]
+pub use graph::{DependencyGraph, Edge, Node};
+pub use key::Key;
+#[cfg(feature = "config")]
+pub use layers::ConfigLayers;
+pub use lifecycle::Lifecycle;
+pub use local::{FromLocalInstanceBuilder, LocalInstanceBuilder};
+pub use module::Module;
+pub use observer::BuildObserver;
+pub use overrides::OverrideGuard;
+#[cfg(feature = "testing")]
+pub use testing::{Lookup, TestBuilder};
+
+use lifecycle::Managed;
+use observer::Observation;
+use slot::OnceSlot;
+use stack::BuildGuard;
+
+#[cfg(feature = "derive")]
+pub use instancebuilder_derive::FromInstanceBuilder;
 
 /// InstanceBuilder offers the creation of configured instances. Due to this pattern, you can for
 /// example use dependency injection in your tests without exposing those.
@@ -38,75 +85,993 @@ use std::fmt::Formatter;
 /// let instance = builder.build::<TestImplementation>().unwrap();
 ///
 /// ```
-pub struct InstanceBuilder {
-    data: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+///
+/// Builders can be nested with [`InstanceBuilder::create_scope`], e.g. to hold per-request data.
+/// A scope resolves data it does not hold itself from its parent.
+pub struct InstanceBuilder<'a> {
+    data: HashMap<TypeId, DataEntry>,
+    named: HashMap<TypeId, HashMap<String, Box<dyn Any + Send + Sync>>>,
+    once: HashMap<TypeId, Mutex<Option<Box<dyn Any + Send>>>>,
+    bindings: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    instances: Mutex<HashMap<TypeId, CachedInstance>>,
+    parent: Option<&'a InstanceBuilder<'a>>,
+    strict: bool,
+    observer: Option<Arc<dyn BuildObserver>>,
+    managed: Mutex<Vec<Managed>>,
+    modules: HashSet<TypeId>,
+}
+
+type SharedInstance = Arc<dyn Any + Send + Sync>;
+
+/// Instance cached by [`InstanceBuilder::build_shared`] and its variants.
+struct CachedInstance {
+    slot: Arc<OnceSlot<SharedInstance>>,
+    /// Declared dependencies of the instance, see [`InstanceBuilder::override_guard`].
+    dependencies: fn() -> Vec<Dependency>,
+    /// Started by [`InstanceBuilder::build_managed`], never dropped from the cache.
+    managed: bool,
+}
+
+type FactoryFn =
+    dyn Fn(&InstanceBuilder<'_>) -> Result<Box<dyn Any + Send + Sync>, BuilderError> + Send + Sync;
+
+enum DataEntry {
+    Value(Box<dyn Any + Send + Sync>),
+    /// Data handed out as `Arc` by [`ConcurrentInstanceBuilder::data`].
+    Shared(SharedInstance),
+    Factory {
+        factory: Box<FactoryFn>,
+        /// Created data, shared like [`DataEntry::Shared`].
+        value: OnceSlot<SharedInstance>,
+        /// Declared dependencies, `None` if the factory did not declare any.
+        dependencies: Option<Vec<Dependency>>,
+    },
+}
+
+impl DataEntry {
+    /// Returns the stored data, a factory returns its data only if it has been called already.
+    /// Shared data is only returned if it isn't shared anymore.
+    fn into_data<D: Any + Send + Sync>(self) -> Option<D> {
+        let data = match self {
+            DataEntry::Value(data) => return data.downcast().ok().map(|data| *data),
+            DataEntry::Shared(data) => data,
+            DataEntry::Factory { value, .. } => value.into_inner()?,
+        };
+
+        Arc::try_unwrap(data.downcast().ok()?).ok()
+    }
 }
 
-impl InstanceBuilder {
+/// Values registered by [`InstanceBuilder::insert_many`], stored in `data` by its own type id.
+/// Entries are ordered by descending priority, then by registration.
+struct Multi<D> {
+    entries: Vec<(i32, D)>,
+}
+
+/// Implementation bound to the (unsized) interface type `I`, stored type erased in `bindings`.
+enum Binding<I: ?Sized> {
+    Instance(Arc<I>),
+    Factory(Box<BindingFn<I>>),
+}
+
+type BindingFn<I> = dyn Fn(&InstanceBuilder<'_>) -> Result<Arc<I>, BuilderError> + Send + Sync;
+
+/// Converts an implementation `T` into the interface `I` it is bound to.
+pub(crate) type CoerceFn<T, I> = fn(Arc<T>) -> Arc<I>;
+
+impl<'a> InstanceBuilder<'a> {
     pub fn new() -> Self {
         Self {
             data: Default::default(),
+            named: Default::default(),
+            once: Default::default(),
+            bindings: Default::default(),
+            instances: Default::default(),
+            parent: None,
+            strict: false,
+            observer: None,
+            managed: Default::default(),
+            modules: Default::default(),
+        }
+    }
+
+    /// Creates a builder in strict mode, see [`InstanceBuilder::set_strict`].
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::new()
+        }
+    }
+
+    /// Enables or disables strict mode.
+    ///
+    /// In strict mode, registering data, factories or bindings for a type that is already
+    /// registered in this builder is an error instead of replacing the previous registration.
+    /// Registrations returning a `Result`, like the config loaders, fail with
+    /// [`BuilderError::AlreadyRegistered`], while the infallible ones like
+    /// [`InstanceBuilder::insert`] behave like their `try_` variant, e.g.
+    /// [`InstanceBuilder::try_insert`], and panic as a last resort. Scopes inherit the mode, but
+    /// may still shadow registrations of their parent.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets the observer notified about builds, replacing the previous one. Scopes created
+    /// afterwards inherit the observer.
+    pub fn set_observer(&mut self, observer: impl BuildObserver + 'static) {
+        self.observer = Some(Arc::new(observer));
+    }
+
+    /// Creates a child builder that falls back to this builder for data it does not hold.
+    ///
+    /// Data inserted into the scope shadows the data of the parent without modifying it.
+    /// Instances built by [`InstanceBuilder::build_scoped`] are cached in the scope and dropped
+    /// with it, while [`InstanceBuilder::build_shared`] still refers to the root builder.
+    pub fn create_scope(&self) -> InstanceBuilder<'_> {
+        InstanceBuilder {
+            data: Default::default(),
+            named: Default::default(),
+            once: Default::default(),
+            bindings: Default::default(),
+            instances: Default::default(),
+            parent: Some(self),
+            strict: self.strict,
+            observer: self.observer.clone(),
+            managed: Default::default(),
+            modules: Default::default(),
         }
     }
 
-    pub fn insert<D: Any + Send + Sync>(&mut self, data: D) {
-        self.data.insert(TypeId::of::<D>(), Box::new(data));
+    /// Inserts data, returning the data previously registered for `D` in this builder.
+    ///
+    /// A replaced factory returns its data only if it has been called already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `D` is already registered and the builder is in strict mode, as a last resort
+    /// since `insert` can't report the error. Use [`InstanceBuilder::try_insert`] to handle it.
+    pub fn insert<D: Any + Send + Sync>(&mut self, data: D) -> Option<D> {
+        self.insert_checked(data)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Inserts data like [`InstanceBuilder::insert`], but fails with
+    /// [`BuilderError::AlreadyRegistered`] instead of panicking in strict mode.
+    pub(crate) fn insert_checked<D: Any + Send + Sync>(
+        &mut self,
+        data: D,
+    ) -> Result<Option<D>, BuilderError> {
+        let previous = self.replace(|builder| builder.remove::<D>());
+        self.try_insert(data)?;
+
+        Ok(previous)
+    }
+
+    /// Inserts data unless data of type `D` is already registered in this builder, handing the
+    /// data back otherwise.
+    pub fn try_insert<D: Any + Send + Sync>(
+        &mut self,
+        data: D,
+    ) -> Result<(), RegistrationError<D>> {
+        Self::check_vacant::<D, _>(self.data.contains_key(&TypeId::of::<D>()), data).map(|data| {
+            self.data
+                .insert(TypeId::of::<D>(), DataEntry::Value(Box::new(data)));
+        })
+    }
+
+    /// Registers a factory which creates the data of type `D` on first access.
+    ///
+    /// The factory is called with the builder it is registered on by the first
+    /// [`InstanceBuilder::data`] lookup of `D`, its result is cached for all further lookups. An
+    /// error of the factory is returned by the lookup and not cached, the next lookup calls the
+    /// factory again.
+    ///
+    /// If `D` implements [`FromInstanceBuilder`], [`InstanceBuilder::build`] creates new
+    /// instances of `D` with the factory as well. Within the factory, `build::<D>()` falls back
+    /// to [`FromInstanceBuilder::try_from_builder`], so a factory may wrap the regular build.
+    pub fn register_factory<D, F>(&mut self, factory: F)
+    where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        self.replace(|builder| builder.data.remove(&TypeId::of::<D>()));
+        self.try_register_factory(factory)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Registers a factory like [`InstanceBuilder::register_factory`] unless `D` is already
+    /// registered in this builder, handing the factory back otherwise.
+    pub fn try_register_factory<D, F>(&mut self, factory: F) -> Result<(), RegistrationError<F>>
+    where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        self.insert_factory(factory, None)
+    }
+
+    /// Registers a factory like [`InstanceBuilder::register_factory`], declaring the data and
+    /// instances it looks up.
+    ///
+    /// The declaration allows [`InstanceBuilder::override_guard`] to keep the created data if
+    /// the factory does not depend on the overridden type.
+    pub fn register_factory_with_dependencies<D, F>(
+        &mut self,
+        dependencies: Vec<Dependency>,
+        factory: F,
+    ) where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        self.replace(|builder| builder.data.remove(&TypeId::of::<D>()));
+        self.try_register_factory_with_dependencies(dependencies, factory)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Registers a factory like [`InstanceBuilder::register_factory_with_dependencies`] unless
+    /// `D` is already registered in this builder, handing the factory back otherwise.
+    pub fn try_register_factory_with_dependencies<D, F>(
+        &mut self,
+        dependencies: Vec<Dependency>,
+        factory: F,
+    ) -> Result<(), RegistrationError<F>>
+    where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        self.insert_factory(factory, Some(dependencies))
+    }
+
+    fn insert_factory<D, F>(
+        &mut self,
+        factory: F,
+        dependencies: Option<Vec<Dependency>>,
+    ) -> Result<(), RegistrationError<F>>
+    where
+        D: Any + Send + Sync,
+        F: Fn(&InstanceBuilder<'_>) -> Result<D, BuilderError> + Send + Sync + 'static,
+    {
+        let factory =
+            Self::check_vacant::<D, _>(self.data.contains_key(&TypeId::of::<D>()), factory)?;
+
+        self.data.insert(
+            TypeId::of::<D>(),
+            DataEntry::Factory {
+                factory: Box::new(move |builder| {
+                    factory(builder).map(|d| Box::new(d) as Box<dyn Any + Send + Sync>)
+                }),
+                value: OnceSlot::new(),
+                dependencies,
+            },
+        );
+
+        Ok(())
     }
 
     pub fn data<D: Any + Send + Sync>(&self) -> Result<&D, BuilderError> {
-        self.data_opt()
+        let data = self.lookup();
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Data,
+            matches!(data, Ok(Some(_))),
+        );
+
+        data?.ok_or_else(|| BuilderError::DataDoesNotExist {
+            ty: type_name::<D>().to_string(),
+        })
+    }
+
+    /// Returns the data of type `D` if present.
+    ///
+    /// A failing factory is treated as missing data, use [`InstanceBuilder::data`] to get its
+    /// error.
+    pub fn data_opt<D: Any + Send + Sync>(&self) -> Option<&D> {
+        let data = self.lookup().ok().flatten();
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Optional,
+            data.is_some(),
+        );
+
+        data
+    }
+
+    /// Returns mutable access to the data of type `D` registered in this builder.
+    ///
+    /// A factory is called first if it has not been called yet. Data of the parent can't be
+    /// modified. Data handed out by [`ConcurrentInstanceBuilder::data`] fails with
+    /// [`BuilderError::DataShared`] while it is held elsewhere. Instances built from the data
+    /// before are not updated.
+    pub fn data_mut<D: Any + Send + Sync>(&mut self) -> Result<&mut D, BuilderError> {
+        if let Some(DataEntry::Factory { .. }) = self.data.get(&TypeId::of::<D>()) {
+            self.lookup::<D>()?;
+        }
+
+        let shared = || BuilderError::DataShared {
+            ty: type_name::<D>().to_string(),
+        };
+        let data = match self.data.get_mut(&TypeId::of::<D>()) {
+            Some(DataEntry::Value(data)) => Some(data.as_mut()),
+            Some(DataEntry::Shared(data)) => Some(Arc::get_mut(data).ok_or_else(shared)?),
+            Some(DataEntry::Factory { value, .. }) => match value.get_mut() {
+                Some(data) => Some(Arc::get_mut(data).ok_or_else(shared)?),
+                None => None,
+            },
+            None => None,
+        };
+
+        data.and_then(|data| data.downcast_mut())
             .ok_or_else(|| BuilderError::DataDoesNotExist {
                 ty: type_name::<D>().to_string(),
             })
     }
 
-    pub fn data_opt<D: Any + Send + Sync>(&self) -> Option<&D> {
+    /// Removes the data of type `D` from this builder, returning it.
+    ///
+    /// Like [`InstanceBuilder::insert`], a removed factory returns its data only if it has been
+    /// called already.
+    pub fn remove<D: Any + Send + Sync>(&mut self) -> Option<D> {
         self.data
+            .remove(&TypeId::of::<D>())
+            .and_then(DataEntry::into_data)
+    }
+
+    /// Inserts data that can be taken out once by [`InstanceBuilder::take`], e.g. the receiver
+    /// of a channel. The data does not need to be `Sync`.
+    pub fn insert_once<D: Any + Send>(&mut self, data: D) {
+        self.replace(|builder| builder.once.remove(&TypeId::of::<D>()));
+        self.try_insert_once(data)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Inserts data like [`InstanceBuilder::insert_once`] unless once-data of type `D` is
+    /// already registered in this builder, handing the data back otherwise.
+    pub fn try_insert_once<D: Any + Send>(&mut self, data: D) -> Result<(), RegistrationError<D>> {
+        Self::check_vacant::<D, _>(self.once.contains_key(&TypeId::of::<D>()), data).map(|data| {
+            self.once
+                .insert(TypeId::of::<D>(), Mutex::new(Some(Box::new(data))));
+        })
+    }
+
+    /// Takes the data inserted by [`InstanceBuilder::insert_once`] out of the builder.
+    ///
+    /// Only the first call for `D` returns the data, further calls fail with
+    /// [`BuilderError::AlreadyTaken`].
+    pub fn take<D: Any + Send>(&self) -> Result<D, BuilderError> {
+        let data = self.take_once();
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Data,
+            data.is_ok(),
+        );
+
+        data
+    }
+
+    fn take_once<D: Any + Send>(&self) -> Result<D, BuilderError> {
+        let Some(once) = self.once.get(&TypeId::of::<D>()) else {
+            return match self.parent {
+                Some(parent) => parent.take_once(),
+                None => Err(BuilderError::DataDoesNotExist {
+                    ty: type_name::<D>().to_string(),
+                }),
+            };
+        };
+
+        once.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .and_then(|data| data.downcast().ok())
+            .map(|data| *data)
+            .ok_or_else(|| BuilderError::AlreadyTaken {
+                ty: type_name::<D>().to_string(),
+            })
+    }
+
+    /// Inserts data under a name, so multiple values of the same type can be registered.
+    ///
+    /// Named data is independent of the data inserted by [`InstanceBuilder::insert`].
+    pub fn insert_named<D: Any + Send + Sync>(&mut self, name: impl Into<String>, data: D) {
+        let name = name.into();
+
+        self.replace(|builder| {
+            builder
+                .named
+                .get_mut(&TypeId::of::<D>())
+                .and_then(|named| named.remove(&name))
+        });
+        self.try_insert_named(name, data)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Inserts data like [`InstanceBuilder::insert_named`] unless data of type `D` is already
+    /// registered under the name in this builder, handing the data back otherwise.
+    pub fn try_insert_named<D: Any + Send + Sync>(
+        &mut self,
+        name: impl Into<String>,
+        data: D,
+    ) -> Result<(), RegistrationError<D>> {
+        let named = self.named.entry(TypeId::of::<D>()).or_default();
+        let name = name.into();
+
+        Self::check_vacant::<D, _>(named.contains_key(&name), data).map(|data| {
+            named.insert(name, Box::new(data));
+        })
+    }
+
+    pub fn data_named<D: Any + Send + Sync>(&self, name: &str) -> Result<&D, BuilderError> {
+        let data = self.named_lookup(name);
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Data,
+            data.is_some(),
+        );
+
+        data.ok_or_else(|| BuilderError::NamedDataDoesNotExist {
+            ty: type_name::<D>().to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    pub fn data_named_opt<D: Any + Send + Sync>(&self, name: &str) -> Option<&D> {
+        let data = self.named_lookup(name);
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Optional,
+            data.is_some(),
+        );
+
+        data
+    }
+
+    fn named_lookup<D: Any + Send + Sync>(&self, name: &str) -> Option<&D> {
+        self.named
             .get(&TypeId::of::<D>())
+            .and_then(|named| named.get(name))
             .and_then(|d| d.downcast_ref::<D>())
+            .or_else(|| self.parent.and_then(|parent| parent.named_lookup(name)))
+    }
+
+    /// Inserts data under a typed [`Key`], see [`InstanceBuilder::insert_named`].
+    pub fn insert_key<D: Any + Send + Sync>(&mut self, key: &Key<D>, data: D) {
+        self.insert_named(key.name(), data);
+    }
+
+    /// Inserts data under a typed [`Key`], see [`InstanceBuilder::try_insert_named`].
+    pub fn try_insert_key<D: Any + Send + Sync>(
+        &mut self,
+        key: &Key<D>,
+        data: D,
+    ) -> Result<(), RegistrationError<D>> {
+        self.try_insert_named(key.name(), data)
+    }
+
+    pub fn data_key<D: Any + Send + Sync>(&self, key: &Key<D>) -> Result<&D, BuilderError> {
+        self.data_named(key.name())
+    }
+
+    pub fn data_key_opt<D: Any + Send + Sync>(&self, key: &Key<D>) -> Option<&D> {
+        self.data_named_opt(key.name())
+    }
+
+    /// Adds data to the set of values of type `D`, see [`InstanceBuilder::data_all`].
+    ///
+    /// The set is independent of the data inserted by [`InstanceBuilder::insert`].
+    pub fn insert_many<D: Any + Send + Sync>(&mut self, data: D) {
+        self.insert_many_with_priority(data, 0);
+    }
+
+    /// Adds data to the set of values of type `D`. Values with a higher priority are returned
+    /// first by [`InstanceBuilder::data_all`], values of equal priority in registration order.
+    pub fn insert_many_with_priority<D: Any + Send + Sync>(&mut self, data: D, priority: i32) {
+        let entry = self
+            .data
+            .entry(TypeId::of::<Multi<D>>())
+            .or_insert_with(|| {
+                DataEntry::Value(Box::new(Multi::<D> {
+                    entries: Vec::new(),
+                }))
+            });
+
+        let DataEntry::Value(multi) = entry else {
+            unreachable!("multi values are never registered by a factory");
+        };
+        let multi = multi
+            .downcast_mut::<Multi<D>>()
+            .expect("multi values are stored by their own type id");
+
+        let pos = multi.entries.partition_point(|(p, _)| *p >= priority);
+        multi.entries.insert(pos, (priority, data));
+    }
+
+    /// Returns all values added by [`InstanceBuilder::insert_many`], followed by the values of the
+    /// parent.
+    pub fn data_all<D: Any + Send + Sync>(&self) -> impl Iterator<Item = &D> {
+        let mut all = Vec::new();
+        let mut builder = Some(self);
+
+        while let Some(current) = builder {
+            if let Some(DataEntry::Value(multi)) = current.data.get(&TypeId::of::<Multi<D>>()) {
+                if let Some(multi) = multi.downcast_ref::<Multi<D>>() {
+                    all.extend(multi.entries.iter().map(|(_, data)| data));
+                }
+            }
+            builder = current.parent;
+        }
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<D>(),
+            DependencyKind::Optional,
+            !all.is_empty(),
+        );
+
+        all.into_iter()
+    }
+
+    /// Returns the factory registered for `D` and the builder it is registered on, unless `D`
+    /// is shadowed by data.
+    fn factory<D: Any>(&self) -> Option<(&InstanceBuilder<'_>, &FactoryFn)> {
+        match self.data.get(&TypeId::of::<D>()) {
+            Some(DataEntry::Factory { factory, .. }) => Some((self, factory.as_ref())),
+            Some(_) => None,
+            None => self.parent?.factory::<D>(),
+        }
+    }
+
+    fn lookup<D: Any + Send + Sync>(&self) -> Result<Option<&D>, BuilderError> {
+        let entry = match self.data.get(&TypeId::of::<D>()) {
+            Some(entry) => entry,
+            None => {
+                return match self.parent {
+                    Some(parent) => parent.lookup(),
+                    None => Ok(None),
+                }
+            }
+        };
+
+        let data: &(dyn Any + Send + Sync) = match entry {
+            DataEntry::Value(data) => data.as_ref(),
+            DataEntry::Shared(data) => data.as_ref(),
+            DataEntry::Factory { factory, value, .. } => match value.get() {
+                Some(data) => data.as_ref(),
+                None => {
+                    let _guard = BuildGuard::enter_factory::<D>()?;
+
+                    value
+                        .get_or_try_init(type_name::<D>(), || {
+                            factory(self)
+                                .map(SharedInstance::from)
+                                .map_err(BuilderError::resolving::<D>)
+                        })?
+                        .as_ref()
+                }
+            },
+        };
+
+        Ok(data.downcast_ref())
+    }
+
+    /// Binds the interface `I`, usually a `dyn Trait`, to the implementation `T`.
+    ///
+    /// [`InstanceBuilder::resolve`] builds `T` on first use and caches it in this builder. The
+    /// `coerce` function converts the instance into the interface, since unsized coercion can't
+    /// be expressed generically, passing the identity closure is sufficient:
+    ///
+    /// ```
+    /// use ::instancebuilder::{BuilderError, InstanceBuilder, FromInstanceBuilder};
+    ///
+    /// trait Repo: Send + Sync {
+    ///     fn name(&self) -> &str;
+    /// }
+    ///
+    /// struct PostgresRepo;
+    ///
+    /// impl Repo for PostgresRepo {
+    ///     fn name(&self) -> &str {
+    ///         "postgres"
+    ///     }
+    /// }
+    ///
+    /// impl FromInstanceBuilder for PostgresRepo {
+    ///     fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+    ///         Ok(Self)
+    ///     }
+    /// }
+    ///
+    /// let mut builder = InstanceBuilder::new();
+    /// builder.bind::<dyn Repo, PostgresRepo>(|repo| repo);
+    ///
+    /// let repo = builder.resolve::<dyn Repo>().unwrap();
+    /// assert_eq!(repo.name(), "postgres");
+    /// ```
+    pub fn bind<I, T>(&mut self, coerce: CoerceFn<T, I>)
+    where
+        I: ?Sized + Send + Sync + 'static,
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        self.replace(|builder| builder.bindings.remove(&TypeId::of::<I>()));
+        self.try_bind::<I, T>(coerce)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Binds the interface `I` like [`InstanceBuilder::bind`] unless `I` is already bound in
+    /// this builder, handing the `coerce` function back otherwise.
+    pub fn try_bind<I, T>(
+        &mut self,
+        coerce: CoerceFn<T, I>,
+    ) -> Result<(), RegistrationError<CoerceFn<T, I>>>
+    where
+        I: ?Sized + Send + Sync + 'static,
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        let coerce =
+            Self::check_vacant::<I, _>(self.bindings.contains_key(&TypeId::of::<I>()), coerce)?;
+        let factory: Box<BindingFn<I>> =
+            Box::new(move |builder| builder.build_scoped::<T>().map(coerce));
+
+        self.bindings
+            .insert(TypeId::of::<I>(), Box::new(Binding::Factory(factory)));
+
+        Ok(())
+    }
+
+    /// Binds the interface `I`, usually a `dyn Trait`, to an existing instance.
+    pub fn insert_dyn<I>(&mut self, instance: Arc<I>)
+    where
+        I: ?Sized + Send + Sync + 'static,
+    {
+        self.replace(|builder| builder.bindings.remove(&TypeId::of::<I>()));
+        self.try_insert_dyn(instance)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Binds the interface `I` to an existing instance unless `I` is already bound in this
+    /// builder, handing the instance back otherwise.
+    pub fn try_insert_dyn<I>(&mut self, instance: Arc<I>) -> Result<(), RegistrationError<Arc<I>>>
+    where
+        I: ?Sized + Send + Sync + 'static,
+    {
+        Self::check_vacant::<I, _>(self.bindings.contains_key(&TypeId::of::<I>()), instance).map(
+            |instance| {
+                self.bindings
+                    .insert(TypeId::of::<I>(), Box::new(Binding::Instance(instance)));
+            },
+        )
+    }
+
+    /// Removes the previous registration with `remove` unless the builder is in strict mode,
+    /// in which case the following `try_` registration fails instead.
+    fn replace<R>(&mut self, remove: impl FnOnce(&mut Self) -> Option<R>) -> Option<R> {
+        if self.strict {
+            return None;
+        }
+
+        remove(self)
+    }
+
+    /// Hands `value` back if `D` is `registered` already.
+    fn check_vacant<D: ?Sized, V>(registered: bool, value: V) -> Result<V, RegistrationError<V>> {
+        if registered {
+            return Err(RegistrationError::new::<D>(value));
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the implementation bound to the interface `I`.
+    ///
+    /// Bindings of the parent are used if this builder has no binding for `I`.
+    pub fn resolve<I>(&self) -> Result<Arc<I>, BuilderError>
+    where
+        I: ?Sized + Send + Sync + 'static,
+    {
+        let binding = self.binding::<I>();
+        observer::data_lookup(
+            self.observer.as_deref(),
+            type_name::<I>(),
+            DependencyKind::Data,
+            binding.is_some(),
+        );
+
+        match binding {
+            Some((_, Binding::Instance(instance))) => Ok(instance.clone()),
+            Some((owner, Binding::Factory(factory))) => {
+                factory(owner).map_err(BuilderError::resolving::<I>)
+            }
+            None => Err(BuilderError::DataDoesNotExist {
+                ty: type_name::<I>().to_string(),
+            }),
+        }
+    }
+
+    /// Returns the binding of `I` and the builder it is registered on.
+    fn binding<I>(&self) -> Option<(&InstanceBuilder<'_>, &Binding<I>)>
+    where
+        I: ?Sized + Send + Sync + 'static,
+    {
+        let binding = self
+            .bindings
+            .get(&TypeId::of::<I>())
+            .and_then(|b| b.downcast_ref::<Binding<I>>());
+
+        match binding {
+            Some(binding) => Some((self, binding)),
+            None => self.parent?.binding(),
+        }
     }
 
+    /// Builds a new instance of `T`.
+    ///
+    /// Nested builds are tracked, a type that (indirectly) depends on itself fails with
+    /// [`BuilderError::CyclicDependency`]. Other errors are wrapped in a
+    /// [`BuilderError::Resolution`] naming the types that were being built, use
+    /// [`BuilderError::root`] to get the original error.
     pub fn build<T>(&self) -> Result<T, BuilderError>
     where
-        T: FromInstanceBuilder,
+        T: FromInstanceBuilder + 'static,
     {
-        T::try_from_builder(self)
+        let factory = self
+            .factory::<T>()
+            .filter(|_| !BuildGuard::in_factory::<T>());
+        let _guard = match factory {
+            Some(_) => BuildGuard::enter_factory::<T>()?,
+            None => BuildGuard::enter::<T>()?,
+        };
+
+        let observation = Observation::start(self.observer.as_deref(), type_name::<T>());
+        let result = observation
+            .in_scope(|| match factory {
+                Some((owner, factory)) => factory(owner).map(|data| {
+                    *data
+                        .downcast::<T>()
+                        .expect("factory data is stored by its own type id")
+                }),
+                None => T::try_from_builder(self),
+            })
+            .map_err(BuilderError::resolving::<T>);
+        observation.finish(&result);
+
+        result
     }
-}
 
-impl Default for InstanceBuilder {
-    fn default() -> Self {
-        Self::new()
+    /// Checks the declared [`FromInstanceBuilder::dependencies`] of `T` against the registered
+    /// data, reporting all failed dependencies at once without building anything.
+    ///
+    /// The whole declared graph of `T` is walked, the returned [`BuilderError::Multiple`]
+    /// contains one error per missing data type, wrapped in the path of the type that declared
+    /// it, and one [`BuilderError::CyclicDependency`] per cycle. Only declared dependencies can
+    /// be checked, derived implementations declare all of their fields.
+    pub fn check<T>(&self) -> Result<(), BuilderError>
+    where
+        T: FromInstanceBuilder + 'static,
+    {
+        let mut errors = Vec::new();
+        let mut path = vec![(TypeId::of::<T>(), type_name::<T>())];
+
+        self.check_dependencies(T::dependencies(), &mut path, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(BuilderError::Multiple(errors))
+        }
     }
-}
 
-#[derive(Debug)]
-pub enum BuilderError {
-    DataDoesNotExist { ty: String },
-    Other(String),
-}
+    fn check_dependencies(
+        &self,
+        dependencies: Vec<Dependency>,
+        path: &mut Vec<(TypeId, &'static str)>,
+        errors: &mut Vec<BuilderError>,
+    ) {
+        let names = |path: &[(TypeId, &'static str)]| {
+            path.iter()
+                .map(|(_, name)| name.to_string())
+                .collect::<Vec<_>>()
+        };
 
-impl ::std::error::Error for BuilderError {}
+        for dependency in dependencies {
+            match dependency.kind() {
+                DependencyKind::Data if !self.contains(dependency.type_id()) => {
+                    errors.push(BuilderError::Resolution {
+                        path: names(path),
+                        source: Box::new(BuilderError::DataDoesNotExist {
+                            ty: dependency.type_name().to_string(),
+                        }),
+                    });
+                }
+                DependencyKind::Build => {
+                    if let Some(pos) = path.iter().position(|(id, _)| *id == dependency.type_id()) {
+                        let mut cycle = names(&path[pos..]);
+                        cycle.push(dependency.type_name().to_string());
+                        errors.push(BuilderError::CyclicDependency { path: cycle });
+                        continue;
+                    }
 
-impl ::std::fmt::Display for BuilderError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BuilderError::DataDoesNotExist { ty } => write!(f, "data of type {ty} does not exist"),
-            BuilderError::Other(err) => {
-                write!(f, "other error: {err}")
+                    path.push((dependency.type_id(), dependency.type_name()));
+                    self.check_dependencies(dependency.dependencies(), path, errors);
+                    path.pop();
+                }
+                DependencyKind::Data | DependencyKind::Optional => {}
             }
         }
     }
+
+    fn contains(&self, type_id: TypeId) -> bool {
+        self.data.contains_key(&type_id) || self.parent.is_some_and(|p| p.contains(type_id))
+    }
+
+    /// Returns the shared instance of `T`, building it on first use.
+    ///
+    /// The first successful build is cached in the root builder, subsequent calls return the
+    /// same `Arc`, also from within scopes. The instance is built by the root builder and thus
+    /// can't depend on data of a scope. Concurrent callers wait for the running build instead of
+    /// building `T` twice. A failed build is not cached.
+    pub fn build_shared<T>(&self) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        match self.parent {
+            Some(parent) => parent.build_shared(),
+            None => self.build_cached(),
+        }
+    }
+
+    /// Returns the instance of `T` cached in this scope, building it on first use.
+    ///
+    /// Behaves like [`InstanceBuilder::build_shared`], but the instance is built and cached by
+    /// this builder. On a root builder both are equivalent.
+    pub fn build_scoped<T>(&self) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        self.build_cached()
+    }
+
+    /// Builds a new instance of `T` asynchronously.
+    ///
+    /// The builder does not depend on a specific runtime, the returned future can be awaited by
+    /// any executor. Like [`InstanceBuilder::build`], a type that depends on itself, directly or
+    /// through other async or sync builds, fails with [`BuilderError::CyclicDependency`].
+    pub async fn build_async<T>(&self) -> Result<T, BuilderError>
+    where
+        T: AsyncFromInstanceBuilder + 'static,
+    {
+        let build = BuildGuard::track::<T, _>(T::try_from_builder_async(self))?;
+
+        let observation = Observation::start(self.observer.as_deref(), type_name::<T>());
+        #[cfg(feature = "tracing")]
+        let build = tracing::Instrument::instrument(build, observation.span().clone());
+        let result = build.await.map_err(BuilderError::resolving::<T>);
+        observation.finish(&result);
+
+        result
+    }
+
+    fn build_cached<T>(&self) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        self.build_cached_with(|_| Ok(()))
+    }
+
+    /// Returns the instance of `T` cached in this builder, `init` is called after building it.
+    fn build_cached_with<T>(
+        &self,
+        init: impl FnOnce(&Arc<T>) -> Result<(), BuilderError>,
+    ) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Send + Sync + 'static,
+    {
+        let slot = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| CachedInstance {
+                slot: Default::default(),
+                dependencies: T::dependencies,
+                managed: false,
+            })
+            .slot
+            .clone();
+
+        let instance = match slot.get() {
+            Some(instance) => {
+                observer::cache_hit(self.observer.as_deref(), type_name::<T>());
+                instance
+            }
+            None => {
+                // Entered before initializing, a cycle must not block on its own slot.
+                let _guard = BuildGuard::enter::<T>()?;
+
+                slot.get_or_try_init(type_name::<T>(), || {
+                    let observation =
+                        Observation::start(self.observer.as_deref(), type_name::<T>());
+                    let result = observation
+                        .in_scope(|| T::try_from_builder(self))
+                        .map(Arc::new)
+                        .map_err(BuilderError::resolving::<T>);
+                    observation.finish(&result);
+
+                    let instance = result?;
+                    init(&instance)?;
+
+                    Ok(instance as SharedInstance)
+                })?
+            }
+        };
+
+        Ok(instance
+            .clone()
+            .downcast::<T>()
+            .expect("shared instance is stored by its own type id"))
+    }
+}
+
+impl Default for InstanceBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub trait FromInstanceBuilder: Sized {
     fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError>;
+
+    /// Declares the dependencies `try_from_builder` resolves, used by
+    /// [`InstanceBuilder::check`] to inspect the dependency graph without building it.
+    ///
+    /// Declaring dependencies is optional, the derive macro generates them from the fields.
+    fn dependencies() -> Vec<Dependency> {
+        Vec::new()
+    }
+}
+
+/// Async counterpart of [`FromInstanceBuilder`] for types that require async initialization,
+/// built by [`InstanceBuilder::build_async`].
+///
+/// Implementations can use `async fn`, the returned future must be `Send`:
+///
+/// ```
+/// use ::instancebuilder::{AsyncFromInstanceBuilder, BuilderError, InstanceBuilder};
+///
+/// struct Pool {
+///     url: String,
+/// }
+///
+/// impl AsyncFromInstanceBuilder for Pool {
+///     async fn try_from_builder_async(builder: &InstanceBuilder<'_>) -> Result<Self, BuilderError> {
+///         let url: &String = builder.data()?;
+///         // connect(url).await
+///         Ok(Self { url: url.clone() })
+///     }
+/// }
+/// ```
+pub trait AsyncFromInstanceBuilder: Sized {
+    fn try_from_builder_async(
+        builder: &InstanceBuilder<'_>,
+    ) -> impl Future<Output = Result<Self, BuilderError>> + Send;
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{BuilderError, FromInstanceBuilder, InstanceBuilder};
-    use std::any::{Any, TypeId};
+    use super::{
+        AsyncFromInstanceBuilder, BuildObserver, BuilderError, Container, Dependency,
+        FromInstanceBuilder, InstanceBuilder, Key, Lifecycle, Module, ResultExt,
+    };
+    use std::any::{type_name, Any, TypeId};
+    use std::error::Error;
+    use std::future::Future;
+    use std::num::ParseIntError;
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
 
     struct TestImplementation {
         inner: String,
@@ -139,4 +1104,1207 @@ mod tests {
         assert_eq!(instance.type_id(), TypeId::of::<TestImplementation>());
         assert_eq!(instance.inner, config_key);
     }
+
+    struct CyclicA;
+    struct CyclicB;
+
+    impl FromInstanceBuilder for CyclicA {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            builder.build::<CyclicB>()?;
+            Ok(Self)
+        }
+    }
+
+    impl FromInstanceBuilder for CyclicB {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            builder.build::<CyclicA>()?;
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn it_detects_cyclic_dependencies() {
+        let builder = InstanceBuilder::new();
+
+        let err = builder.build::<CyclicA>().err().unwrap();
+
+        let BuilderError::CyclicDependency { path } = err else {
+            panic!("expected cyclic dependency, got {err}");
+        };
+        assert_eq!(
+            path,
+            vec![
+                type_name::<CyclicA>(),
+                type_name::<CyclicB>(),
+                type_name::<CyclicA>()
+            ]
+        );
+
+        // the build stack is unwound after the failure, the next cycle starts at its own root
+        let Some(BuilderError::CyclicDependency { path }) = builder.build::<CyclicB>().err() else {
+            panic!("expected cyclic dependency");
+        };
+        assert_eq!(
+            path.first().map(String::as_str),
+            Some(type_name::<CyclicB>())
+        );
+    }
+
+    static SHARED_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    struct SharedImplementation;
+
+    impl FromInstanceBuilder for SharedImplementation {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            SHARED_BUILDS.fetch_add(1, Ordering::SeqCst);
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn it_builds_shared_instances_once() {
+        let builder = InstanceBuilder::new();
+
+        let instances = std::thread::scope(|s| {
+            let handles = (0..8)
+                .map(|_| s.spawn(|| builder.build_shared::<SharedImplementation>().unwrap()))
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(SHARED_BUILDS.load(Ordering::SeqCst), 1);
+        assert!(instances.iter().all(|i| Arc::ptr_eq(i, &instances[0])));
+    }
+
+    struct CrossThreadA;
+    struct CrossThreadB;
+
+    impl FromInstanceBuilder for CrossThreadA {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            // Gives the other thread time to start building `CrossThreadB`.
+            std::thread::sleep(Duration::from_millis(100));
+            builder.build_shared::<CrossThreadB>()?;
+            Ok(Self)
+        }
+    }
+
+    impl FromInstanceBuilder for CrossThreadB {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            std::thread::sleep(Duration::from_millis(100));
+            builder.build_shared::<CrossThreadA>()?;
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn it_detects_cyclic_dependencies_across_threads() {
+        let container = InstanceBuilder::new().freeze();
+        let (sender, receiver) = channel();
+
+        {
+            let (container, sender) = (container.clone(), sender.clone());
+            std::thread::spawn(move || {
+                let result = container.build_shared::<CrossThreadA>().map(drop);
+                sender.send(result).unwrap();
+            });
+        }
+        std::thread::spawn(move || {
+            let result = container.build_shared::<CrossThreadB>().map(drop);
+            sender.send(result).unwrap();
+        });
+
+        for _ in 0..2 {
+            let result = receiver
+                .recv_timeout(Duration::from_secs(5))
+                .expect("builds waiting for each other deadlocked");
+            assert!(matches!(result, Err(BuilderError::CyclicDependency { .. })));
+        }
+    }
+
+    #[test]
+    fn it_does_not_cache_failed_shared_builds() {
+        let mut builder = InstanceBuilder::new();
+
+        assert!(builder.build_shared::<TestImplementation>().is_err());
+
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+
+        let instance = builder.build_shared::<TestImplementation>().unwrap();
+        assert_eq!(instance.inner, "help me!");
+    }
+
+    #[test]
+    fn it_resolves_data_through_scopes() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("parent"),
+        });
+        builder.insert(42usize);
+
+        let mut scope = builder.create_scope();
+        scope.insert(TestConfig {
+            key: String::from("scope"),
+        });
+
+        assert_eq!(scope.build::<TestImplementation>().unwrap().inner, "scope");
+        assert_eq!(scope.data::<usize>().unwrap(), &42);
+        assert_eq!(
+            builder.build::<TestImplementation>().unwrap().inner,
+            "parent"
+        );
+    }
+
+    #[test]
+    fn it_caches_scoped_instances_per_scope() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("parent"),
+        });
+        let shared = builder.build_shared::<TestImplementation>().unwrap();
+
+        let first = builder.create_scope();
+        let second = builder.create_scope();
+
+        let first_scoped = first.build_scoped::<TestImplementation>().unwrap();
+        assert!(Arc::ptr_eq(
+            &first_scoped,
+            &first.build_scoped::<TestImplementation>().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            &first_scoped,
+            &second.build_scoped::<TestImplementation>().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            &shared,
+            &second.build_shared::<TestImplementation>().unwrap()
+        ));
+    }
+
+    #[test]
+    fn it_creates_factory_data_lazily_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(String::from("help me!"));
+        builder.register_factory({
+            let calls = calls.clone();
+            move |builder| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(TestConfig {
+                    key: builder.data::<String>()?.clone(),
+                })
+            }
+        });
+
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        assert_eq!(
+            builder.build::<TestImplementation>().unwrap().inner,
+            "help me!"
+        );
+        assert_eq!(builder.data::<TestConfig>().unwrap().key, "help me!");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_builds_with_factories_of_the_built_type() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("pool"),
+        });
+        builder.register_factory({
+            let calls = calls.clone();
+            move |builder| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                builder.build::<TestImplementation>()
+            }
+        });
+
+        let scope = builder.create_scope();
+        assert_eq!(scope.data::<TestImplementation>().unwrap().inner, "pool");
+        assert_eq!(scope.data::<TestImplementation>().unwrap().inner, "pool");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(scope.build::<TestImplementation>().unwrap().inner, "pool");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    trait Greeter: Send + Sync {
+        fn greet(&self) -> String;
+    }
+
+    impl Greeter for TestImplementation {
+        fn greet(&self) -> String {
+            self.inner.clone()
+        }
+    }
+
+    struct MockGreeter;
+
+    impl Greeter for MockGreeter {
+        fn greet(&self) -> String {
+            String::from("mock")
+        }
+    }
+
+    struct GreeterConsumer {
+        greeter: Arc<dyn Greeter>,
+    }
+
+    impl FromInstanceBuilder for GreeterConsumer {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self {
+                greeter: builder.resolve()?,
+            })
+        }
+    }
+
+    #[test]
+    fn it_resolves_trait_object_bindings() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+
+        assert!(matches!(
+            builder.build::<GreeterConsumer>().err().unwrap().root(),
+            BuilderError::DataDoesNotExist { ty } if ty == type_name::<dyn Greeter>()
+        ));
+
+        builder.bind::<dyn Greeter, TestImplementation>(|greeter| greeter);
+
+        let consumer = builder.build::<GreeterConsumer>().unwrap();
+        assert_eq!(consumer.greeter.greet(), "help me!");
+        assert!(Arc::ptr_eq(
+            &consumer.greeter,
+            &builder.resolve::<dyn Greeter>().unwrap()
+        ));
+
+        let mut scope = builder.create_scope();
+        scope.insert_dyn::<dyn Greeter>(Arc::new(MockGreeter));
+        assert_eq!(
+            scope.build::<GreeterConsumer>().unwrap().greeter.greet(),
+            "mock"
+        );
+    }
+
+    #[test]
+    fn it_keeps_named_data_apart() {
+        const REPLICA: Key<String> = Key::new("replica");
+
+        let mut builder = InstanceBuilder::new();
+        builder.insert(String::from("default"));
+        builder.insert_named("primary", String::from("primary"));
+        builder.insert_key(&REPLICA, String::from("replica"));
+
+        let scope = builder.create_scope();
+
+        assert_eq!(scope.data::<String>().unwrap(), "default");
+        assert_eq!(scope.data_named::<String>("primary").unwrap(), "primary");
+        assert_eq!(scope.data_key(&REPLICA).unwrap(), "replica");
+        assert!(matches!(
+            scope.data_named::<String>("other"),
+            Err(BuilderError::NamedDataDoesNotExist { name, .. }) if name == "other"
+        ));
+        assert!(scope.data_named_opt::<usize>("primary").is_none());
+    }
+
+    #[test]
+    fn it_collects_multiple_values_by_priority() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert_many("auth");
+        builder.insert_many_with_priority("tracing", 10);
+        builder.insert_many("compression");
+        builder.insert("single");
+
+        let mut scope = builder.create_scope();
+        scope.insert_many("request");
+
+        assert_eq!(
+            builder.data_all::<&str>().copied().collect::<Vec<_>>(),
+            vec!["tracing", "auth", "compression"]
+        );
+        assert_eq!(
+            scope.data_all::<&str>().copied().collect::<Vec<_>>(),
+            vec!["request", "tracing", "auth", "compression"]
+        );
+        assert_eq!(builder.data::<&str>().unwrap(), &"single");
+        assert_eq!(builder.data_all::<usize>().count(), 0);
+    }
+
+    #[test]
+    fn it_reports_replaced_data() {
+        let mut builder = InstanceBuilder::new();
+
+        assert_eq!(builder.insert(1usize), None);
+        assert_eq!(builder.insert(2usize), Some(1));
+        let err = builder.try_insert(3usize).err().unwrap();
+        assert_eq!(err.value(), &3);
+        assert!(matches!(
+            BuilderError::from(err),
+            BuilderError::AlreadyRegistered { ty } if ty == type_name::<usize>()
+        ));
+        assert_eq!(builder.data::<usize>().unwrap(), &2);
+
+        builder.register_factory(|_| Ok(4usize));
+        assert_eq!(builder.insert(5usize), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "data of type usize is already registered")]
+    fn it_panics_on_replaced_data_in_strict_mode() {
+        let mut builder = InstanceBuilder::strict();
+        builder.insert(1usize);
+
+        let mut scope = builder.create_scope();
+        scope.insert(2usize);
+        scope.insert(3usize);
+    }
+
+    #[test]
+    fn it_hands_back_rejected_registrations() {
+        const PRIMARY: Key<String> = Key::new("primary");
+
+        let mut builder = InstanceBuilder::new();
+        builder.register_factory(|_| Ok(1usize));
+        builder.insert_once(2u8);
+        builder.insert_key(&PRIMARY, String::from("primary"));
+        builder.insert_dyn::<dyn Greeter>(Arc::new(MockGreeter));
+
+        let factory = builder
+            .try_register_factory(|_| Ok(3usize))
+            .err()
+            .unwrap()
+            .into_value();
+        assert_eq!(factory(&builder).unwrap(), 3);
+        assert!(builder
+            .try_register_factory_with_dependencies(Vec::new(), |_| Ok(3usize))
+            .is_err());
+        assert_eq!(builder.try_insert_once(4u8).err().unwrap().into_value(), 4);
+        assert_eq!(
+            builder
+                .try_insert_key(&PRIMARY, String::from("other"))
+                .err()
+                .unwrap()
+                .into_value(),
+            "other"
+        );
+        assert!(builder
+            .try_bind::<dyn Greeter, TestImplementation>(|greeter| greeter)
+            .is_err());
+        let rejected = builder
+            .try_insert_dyn::<dyn Greeter>(Arc::new(MockGreeter))
+            .err()
+            .unwrap();
+        assert_eq!(rejected.value().greet(), "mock");
+
+        assert_eq!(builder.data::<usize>().unwrap(), &1);
+        assert_eq!(builder.take::<u8>().unwrap(), 2);
+        assert_eq!(builder.data_key(&PRIMARY).unwrap(), "primary");
+        assert!(builder.try_insert_named("secondary", String::new()).is_ok());
+    }
+
+    /// Minimal executor, polls the future until it completes.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Future that is pending once before it completes.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    struct AsyncPool {
+        url: String,
+    }
+
+    impl AsyncFromInstanceBuilder for AsyncPool {
+        async fn try_from_builder_async(
+            builder: &InstanceBuilder<'_>,
+        ) -> Result<Self, BuilderError> {
+            let config: &TestConfig = builder.data()?;
+            YieldNow(false).await;
+
+            Ok(Self {
+                url: config.key.clone(),
+            })
+        }
+    }
+
+    struct AsyncRepository {
+        pool: AsyncPool,
+        sync: TestImplementation,
+    }
+
+    impl AsyncFromInstanceBuilder for AsyncRepository {
+        async fn try_from_builder_async(
+            builder: &InstanceBuilder<'_>,
+        ) -> Result<Self, BuilderError> {
+            Ok(Self {
+                pool: builder.build_async().await?,
+                sync: builder.build()?,
+            })
+        }
+    }
+
+    #[test]
+    fn it_builds_async_instances() {
+        let mut builder = InstanceBuilder::new();
+
+        assert!(block_on(builder.build_async::<AsyncRepository>()).is_err());
+
+        builder.insert(TestConfig {
+            key: String::from("postgres://"),
+        });
+
+        let repository = block_on(builder.build_async::<AsyncRepository>()).unwrap();
+        assert_eq!(repository.pool.url, "postgres://");
+        assert_eq!(repository.sync.inner, "postgres://");
+    }
+
+    struct AsyncCyclicA;
+    struct AsyncCyclicB;
+
+    impl AsyncFromInstanceBuilder for AsyncCyclicA {
+        async fn try_from_builder_async(
+            builder: &InstanceBuilder<'_>,
+        ) -> Result<Self, BuilderError> {
+            YieldNow(false).await;
+            builder.build_async::<AsyncCyclicB>().await?;
+            Ok(Self)
+        }
+    }
+
+    impl AsyncFromInstanceBuilder for AsyncCyclicB {
+        // Recursive async types need a type-erased future to compile at all.
+        fn try_from_builder_async(
+            builder: &InstanceBuilder<'_>,
+        ) -> impl Future<Output = Result<Self, BuilderError>> + Send {
+            let build: Pin<Box<dyn Future<Output = _> + Send + '_>> = Box::pin(async move {
+                builder.build_async::<AsyncCyclicA>().await?;
+                Ok(Self)
+            });
+            build
+        }
+    }
+
+    #[test]
+    fn it_detects_cyclic_async_dependencies() {
+        let builder = InstanceBuilder::new();
+
+        let err = block_on(builder.build_async::<AsyncCyclicA>())
+            .err()
+            .unwrap();
+
+        let BuilderError::CyclicDependency { path } = err else {
+            panic!("expected cyclic dependency, got {err}");
+        };
+        assert_eq!(
+            path,
+            vec![
+                type_name::<AsyncCyclicA>(),
+                type_name::<AsyncCyclicB>(),
+                type_name::<AsyncCyclicA>()
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_interleaved_async_builds_apart() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("postgres://"),
+        });
+
+        let mut first = pin!(builder.build_async::<AsyncPool>());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+
+        assert!(block_on(builder.build_async::<AsyncPool>()).is_ok());
+        assert!(block_on(first).is_ok());
+    }
+
+    struct OuterTestImplementation {
+        _inner: TestImplementation,
+    }
+
+    impl FromInstanceBuilder for OuterTestImplementation {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self {
+                _inner: builder.build()?,
+            })
+        }
+    }
+
+    struct Port(u16);
+
+    impl FromInstanceBuilder for Port {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            let port: &String = builder.data()?;
+            Ok(Self(port.parse().construction::<Self>()?))
+        }
+    }
+
+    #[test]
+    fn it_reports_the_resolution_path() {
+        let builder = InstanceBuilder::new();
+
+        let err = builder.build::<OuterTestImplementation>().err().unwrap();
+
+        let BuilderError::Resolution { path, .. } = &err else {
+            panic!("expected resolution error, got {err}");
+        };
+        assert_eq!(
+            path,
+            &vec![
+                type_name::<OuterTestImplementation>(),
+                type_name::<TestImplementation>()
+            ]
+        );
+        assert!(matches!(
+            err.root(),
+            BuilderError::DataDoesNotExist { ty } if ty == type_name::<TestConfig>()
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "failed to build {} -> {}: data of type {} does not exist",
+                type_name::<OuterTestImplementation>(),
+                type_name::<TestImplementation>(),
+                type_name::<TestConfig>()
+            )
+        );
+    }
+
+    struct DeclaredOuter;
+    struct DeclaredInner;
+
+    impl FromInstanceBuilder for DeclaredOuter {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            unreachable!("check must not build instances")
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![
+                Dependency::data::<String>(),
+                Dependency::build::<DeclaredInner>(),
+            ]
+        }
+    }
+
+    impl FromInstanceBuilder for DeclaredInner {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            unreachable!("check must not build instances")
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![Dependency::data::<TestConfig>()]
+        }
+    }
+
+    #[test]
+    fn it_reports_all_missing_dependencies_at_once() {
+        let mut builder = InstanceBuilder::new();
+
+        let Err(BuilderError::Multiple(errors)) = builder.check::<DeclaredOuter>() else {
+            panic!("expected check to fail");
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            &errors[1],
+            BuilderError::Resolution { path, source } if path.len() == 2 && matches!(
+                **source,
+                BuilderError::DataDoesNotExist { ref ty } if ty == type_name::<TestConfig>()
+            )
+        ));
+
+        builder.insert(String::from("outer"));
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+        assert!(builder.check::<DeclaredOuter>().is_ok());
+    }
+
+    struct DeclaredImplementation;
+
+    impl FromInstanceBuilder for DeclaredImplementation {
+        fn try_from_builder(_builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            unreachable!("check must not build instances")
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![
+                Dependency::data::<usize>(),
+                Dependency::optional::<u8>(),
+                Dependency::build::<DeclaredImplementation>(),
+            ]
+        }
+    }
+
+    #[test]
+    fn it_checks_declared_dependencies() {
+        let mut builder = InstanceBuilder::new();
+
+        let Err(BuilderError::Multiple(errors)) = builder.check::<DeclaredImplementation>() else {
+            panic!("expected check to fail");
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0].root(),
+            BuilderError::DataDoesNotExist { ty } if ty == "usize"
+        ));
+        assert!(matches!(&errors[1], BuilderError::CyclicDependency { path } if path.len() == 2));
+
+        builder.register_factory(|_| Ok(1usize));
+        let scope = builder.create_scope();
+        let Err(BuilderError::Multiple(errors)) = scope.check::<DeclaredImplementation>() else {
+            panic!("expected check to fail");
+        };
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn it_keeps_the_source_of_construction_errors() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(String::from("http"));
+
+        let err = builder.build::<Port>().err().unwrap();
+
+        assert!(matches!(
+            err.root(),
+            BuilderError::Construction { ty, .. } if ty == type_name::<Port>()
+        ));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<ParseIntError>().is_some());
+
+        builder.insert(String::from("8080"));
+        assert_eq!(builder.build::<Port>().unwrap().0, 8080);
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl BuildObserver for RecordingObserver {
+        fn build_started(&self, ty: &'static str) {
+            self.events.lock().unwrap().push(format!("started {ty}"));
+        }
+
+        fn build_finished(&self, ty: &'static str, _: Duration, result: Result<(), &BuilderError>) {
+            let outcome = if result.is_ok() { "built" } else { "failed" };
+            self.events.lock().unwrap().push(format!("{outcome} {ty}"));
+        }
+
+        fn cache_hit(&self, ty: &'static str) {
+            self.events.lock().unwrap().push(format!("cached {ty}"));
+        }
+    }
+
+    #[test]
+    fn it_notifies_the_build_observer() {
+        let observer = Arc::new(RecordingObserver::default());
+        let mut builder = InstanceBuilder::new();
+        builder.set_observer(observer.clone());
+
+        assert!(builder.build::<OuterTestImplementation>().is_err());
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+        builder
+            .create_scope()
+            .build_shared::<TestImplementation>()
+            .unwrap();
+        builder.build_shared::<TestImplementation>().unwrap();
+
+        let outer = type_name::<OuterTestImplementation>();
+        let inner = type_name::<TestImplementation>();
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec![
+                format!("started {outer}"),
+                format!("started {inner}"),
+                format!("failed {inner}"),
+                format!("failed {outer}"),
+                format!("started {inner}"),
+                format!("built {inner}"),
+                format!("cached {inner}"),
+            ]
+        );
+    }
+
+    struct Worker {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+        fail_shutdown: bool,
+    }
+
+    impl Lifecycle for Worker {
+        fn start(&self) -> Result<(), BuilderError> {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("start {}", self.name));
+            Ok(())
+        }
+
+        fn shutdown(&self) -> Result<(), BuilderError> {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("shutdown {}", self.name));
+            if self.fail_shutdown {
+                return Err(BuilderError::Other(format!("{} is stuck", self.name)));
+            }
+            Ok(())
+        }
+    }
+
+    struct Database(Worker);
+    struct Server(Worker, #[allow(dead_code)] Arc<Database>);
+
+    impl FromInstanceBuilder for Database {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self(Worker {
+                name: "database",
+                events: builder.data::<Arc<Mutex<Vec<String>>>>()?.clone(),
+                fail_shutdown: true,
+            }))
+        }
+    }
+
+    impl Lifecycle for Database {
+        fn start(&self) -> Result<(), BuilderError> {
+            self.0.start()
+        }
+
+        fn shutdown(&self) -> Result<(), BuilderError> {
+            self.0.shutdown()
+        }
+    }
+
+    impl FromInstanceBuilder for Server {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            let database = builder.build_managed::<Database>()?;
+            Ok(Self(
+                Worker {
+                    name: "server",
+                    events: builder.data::<Arc<Mutex<Vec<String>>>>()?.clone(),
+                    fail_shutdown: false,
+                },
+                database,
+            ))
+        }
+    }
+
+    impl Lifecycle for Server {
+        fn start(&self) -> Result<(), BuilderError> {
+            self.0.start()
+        }
+
+        fn shutdown(&self) -> Result<(), BuilderError> {
+            self.0.shutdown()
+        }
+    }
+
+    #[test]
+    fn it_shuts_down_managed_instances_in_reverse_order() {
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(events.clone());
+
+        let server = builder.create_scope().build_managed::<Server>().unwrap();
+        assert!(Arc::ptr_eq(&server, &builder.build_managed().unwrap()));
+
+        let err = builder.shutdown().err().unwrap();
+        assert!(matches!(
+            err.root(),
+            BuilderError::Other(msg) if msg == "database is stuck"
+        ));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "start database",
+                "start server",
+                "shutdown server",
+                "shutdown database"
+            ]
+        );
+
+        // everything has been shut down already
+        assert!(block_on(builder.shutdown_async()).is_ok());
+    }
+
+    #[test]
+    fn it_starts_shared_instances_when_they_are_managed_later() {
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(events.clone());
+
+        let shared = builder.build_shared::<Database>().unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        let managed = builder.build_managed::<Database>().unwrap();
+        assert!(Arc::ptr_eq(&shared, &managed));
+        builder.build_managed::<Database>().unwrap();
+
+        assert!(builder.shutdown().is_err());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["start database", "shutdown database"]
+        );
+    }
+
+    #[test]
+    fn it_shares_frozen_containers_across_threads() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<Container>();
+
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+        let container = builder.freeze();
+
+        let instances = (0..4)
+            .map(|_| {
+                let container = container.clone();
+                std::thread::spawn(move || container.build_shared::<TestImplementation>().unwrap())
+            })
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(instances.iter().all(|i| Arc::ptr_eq(i, &instances[0])));
+        assert_eq!(
+            container.build::<TestImplementation>().unwrap().inner,
+            "help me!"
+        );
+    }
+
+    #[test]
+    fn it_modifies_and_removes_data() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("help me!"),
+        });
+        builder.register_factory(|_| Ok(1usize));
+
+        builder.data_mut::<TestConfig>().unwrap().key = String::from("changed");
+        *builder.data_mut::<usize>().unwrap() += 1;
+
+        assert_eq!(
+            builder.build::<TestImplementation>().unwrap().inner,
+            "changed"
+        );
+        assert_eq!(builder.remove::<usize>(), Some(2));
+        assert_eq!(builder.remove::<usize>(), None);
+        assert!(builder.data_mut::<usize>().is_err());
+        assert!(builder.create_scope().data_mut::<TestConfig>().is_err());
+    }
+
+    struct Consumer {
+        receiver: Receiver<String>,
+    }
+
+    impl FromInstanceBuilder for Consumer {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self {
+                receiver: builder.take()?,
+            })
+        }
+    }
+
+    #[test]
+    fn it_takes_data_once() {
+        let (sender, receiver) = channel();
+        let mut builder = InstanceBuilder::new();
+        builder.insert_once(receiver);
+
+        let consumer = builder.create_scope().build::<Consumer>().unwrap();
+        sender.send(String::from("help me!")).unwrap();
+        assert_eq!(consumer.receiver.recv().unwrap(), "help me!");
+
+        assert!(matches!(
+            builder.build::<Consumer>().err().unwrap().root(),
+            BuilderError::AlreadyTaken { .. }
+        ));
+        assert!(matches!(
+            InstanceBuilder::new().take::<Receiver<String>>(),
+            Err(BuilderError::DataDoesNotExist { .. })
+        ));
+    }
+
+    #[test]
+    fn it_surfaces_factory_errors() {
+        let mut builder = InstanceBuilder::new();
+        builder.register_factory(|_| -> Result<TestConfig, BuilderError> {
+            Err(BuilderError::Other(String::from("connection refused")))
+        });
+
+        assert!(matches!(
+            builder.data::<TestConfig>().err().unwrap().root(),
+            BuilderError::Other(msg) if msg == "connection refused"
+        ));
+        assert!(builder.data_opt::<TestConfig>().is_none());
+    }
+
+    struct CounterModule;
+
+    impl Module for CounterModule {
+        fn configure(&self, builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+            *builder.data_mut::<u32>()? += 1;
+            Ok(())
+        }
+    }
+
+    struct ConfigModule;
+
+    impl Module for ConfigModule {
+        fn configure(&self, builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+            Ok(builder.try_insert(TestConfig {
+                key: String::from("module"),
+            })?)
+        }
+
+        fn dependencies(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(CounterModule)]
+        }
+    }
+
+    struct AppModule;
+
+    impl Module for AppModule {
+        fn configure(&self, _builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+            Ok(())
+        }
+
+        fn dependencies(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(CounterModule), Box::new(ConfigModule)]
+        }
+    }
+
+    #[test]
+    fn it_installs_modules_once() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(0u32);
+
+        builder.install(AppModule).unwrap();
+        builder.install(CounterModule).unwrap();
+
+        assert_eq!(builder.data::<u32>().unwrap(), &1);
+        assert_eq!(builder.data::<TestConfig>().unwrap().key, "module");
+        assert!(builder.is_installed::<ConfigModule>());
+
+        let mut scope = builder.create_scope();
+        // installed in the parent already, the counter of the scope is not touched
+        scope.insert(0u32);
+        scope.install(AppModule).unwrap();
+        assert_eq!(scope.data::<u32>().unwrap(), &0);
+    }
+
+    #[test]
+    fn it_names_the_failing_module() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(0u32);
+        builder.insert(TestConfig {
+            key: String::from("existing"),
+        });
+
+        let err = builder.install(AppModule).err().unwrap();
+
+        assert!(matches!(
+            &err,
+            BuilderError::Module { name, source }
+                if name.ends_with("ConfigModule")
+                    && matches!(**source, BuilderError::AlreadyRegistered { .. })
+        ));
+        assert!(builder.is_installed::<CounterModule>());
+        assert!(!builder.is_installed::<AppModule>());
+    }
+
+    struct PingModule;
+    struct PongModule;
+
+    impl Module for PingModule {
+        fn configure(&self, _builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+            Ok(())
+        }
+
+        fn dependencies(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(PongModule)]
+        }
+    }
+
+    impl Module for PongModule {
+        fn configure(&self, _builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+            Ok(())
+        }
+
+        fn dependencies(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(PingModule)]
+        }
+    }
+
+    #[test]
+    fn it_detects_cyclic_modules() {
+        let err = InstanceBuilder::new().install(PingModule).err().unwrap();
+
+        let BuilderError::CyclicDependency { path } = err else {
+            panic!("expected cyclic dependency, got {err}");
+        };
+        assert_eq!(path.len(), 3);
+        assert!(path[0].ends_with("PingModule") && path[2].ends_with("PingModule"));
+    }
+
+    #[test]
+    fn it_restores_overridden_data() {
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("real"),
+        });
+        builder.register_factory(|builder| {
+            Ok(format!("factory {}", builder.data::<TestConfig>()?.key))
+        });
+        let real = builder.build_shared::<TestImplementation>().unwrap();
+
+        {
+            let guard = builder.override_guard(TestConfig {
+                key: String::from("mock"),
+            });
+            assert_eq!(
+                guard.build_shared::<TestImplementation>().unwrap().inner,
+                "mock"
+            );
+            assert_eq!(guard.build::<TestImplementation>().unwrap().inner, "mock");
+            assert_eq!(guard.data::<String>().unwrap(), "factory mock");
+        }
+
+        assert_eq!(builder.data::<TestConfig>().unwrap().key, "real");
+        assert_eq!(builder.data::<String>().unwrap(), "factory real");
+        let rebuilt = builder.build_shared::<TestImplementation>().unwrap();
+        assert_eq!(rebuilt.inner, "real");
+        assert!(!Arc::ptr_eq(&real, &rebuilt));
+
+        let inner = builder.with_override(0u8, |builder| {
+            builder.with_override(1u8, |builder| *builder.data::<u8>().unwrap())
+        });
+        assert_eq!(inner, 1);
+        assert!(builder.data_opt::<u8>().is_none());
+    }
+
+    struct Prefixed(String);
+
+    impl FromInstanceBuilder for Prefixed {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self(format!("{}!", builder.data::<String>()?)))
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![Dependency::data::<String>()]
+        }
+    }
+
+    struct Listener(u16);
+
+    impl FromInstanceBuilder for Listener {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self(*builder.data::<u16>()?))
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![Dependency::data::<u16>()]
+        }
+    }
+
+    #[test]
+    fn it_invalidates_only_dependents_of_overrides() {
+        let pools = Arc::new(AtomicUsize::new(0));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(TestConfig {
+            key: String::from("real"),
+        });
+        builder.insert(80u16);
+        builder.register_factory_with_dependencies(
+            vec![Dependency::data::<TestConfig>()],
+            |builder| Ok(format!("factory {}", builder.data::<TestConfig>()?.key)),
+        );
+        builder.register_factory_with_dependencies(Vec::new(), {
+            let pools = pools.clone();
+            move |_| Ok(pools.fetch_add(1, Ordering::SeqCst) as u64)
+        });
+
+        let listener = builder.build_shared::<Listener>().unwrap();
+        assert_eq!(listener.0, 80);
+        assert_eq!(
+            builder.build_shared::<Prefixed>().unwrap().0,
+            "factory real!"
+        );
+        assert_eq!(builder.data::<u64>().unwrap(), &0);
+
+        builder.with_override(
+            TestConfig {
+                key: String::from("mock"),
+            },
+            |builder| {
+                assert_eq!(
+                    builder.build_shared::<Prefixed>().unwrap().0,
+                    "factory mock!"
+                );
+                assert!(Arc::ptr_eq(&listener, &builder.build_shared().unwrap()));
+            },
+        );
+
+        assert_eq!(
+            builder.build_shared::<Prefixed>().unwrap().0,
+            "factory real!"
+        );
+        assert!(Arc::ptr_eq(&listener, &builder.build_shared().unwrap()));
+        assert_eq!(builder.data::<u64>().unwrap(), &0);
+        assert_eq!(pools.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_keeps_managed_instances_across_overrides() {
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let mut builder = InstanceBuilder::new();
+        builder.insert(events.clone());
+
+        let database = builder.build_managed::<Database>().unwrap();
+        let overridden =
+            builder.with_override(2u8, |builder| builder.build_managed::<Database>().unwrap());
+        let restored = builder.build_managed::<Database>().unwrap();
+
+        assert!(Arc::ptr_eq(&database, &overridden));
+        assert!(Arc::ptr_eq(&database, &restored));
+
+        assert!(builder.shutdown().is_err());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["start database", "shutdown database"]
+        );
+    }
 }
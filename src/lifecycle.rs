@@ -0,0 +1,155 @@
+use crate::slot::OnceSlot;
+use crate::stack::BuildGuard;
+use crate::{BuilderError, FromInstanceBuilder, InstanceBuilder, SharedInstance};
+use std::any::{type_name, TypeId};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError};
+
+/// Lifecycle hooks of instances built by [`InstanceBuilder::build_managed`].
+pub trait Lifecycle: Send + Sync {
+    /// Called once after the instance has been built, before it is returned.
+    fn start(&self) -> Result<(), BuilderError> {
+        Ok(())
+    }
+
+    /// Called by [`InstanceBuilder::shutdown`].
+    fn shutdown(&self) -> Result<(), BuilderError> {
+        Ok(())
+    }
+
+    /// Called by [`InstanceBuilder::shutdown_async`], defaults to [`Lifecycle::shutdown`].
+    fn shutdown_async(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<(), BuilderError>> + Send + '_>> {
+        Box::pin(std::future::ready(self.shutdown()))
+    }
+}
+
+/// Started instance, shut down in reverse order of creation.
+pub(crate) type Managed = (&'static str, Arc<dyn Lifecycle>);
+
+impl InstanceBuilder<'_> {
+    /// Returns the shared instance of `T` like [`InstanceBuilder::build_shared`], calling
+    /// [`Lifecycle::start`] after it has been built.
+    ///
+    /// Started instances are shut down by [`InstanceBuilder::shutdown`] of the root builder. A
+    /// failing start hook fails the build, the instance is neither cached nor shut down. `T`
+    /// shares the cache of `build_shared`, an instance built by `build_shared` first is started
+    /// by the first call of `build_managed`. If that start fails, the instance stays cached and
+    /// the next call tries to start it again.
+    pub fn build_managed<T>(&self) -> Result<Arc<T>, BuilderError>
+    where
+        T: FromInstanceBuilder + Lifecycle + 'static,
+    {
+        if let Some(parent) = self.parent {
+            return parent.build_managed();
+        }
+
+        let instance = self.build_cached_with::<T>(|instance| self.start_managed(instance))?;
+
+        if let Some(slot) = self.unmanaged::<T>() {
+            // Entered before locking, a start hook building `T` again must not block on the slot.
+            let _guard = BuildGuard::enter::<T>()?;
+            let _lock = slot.lock()?;
+
+            if self.unmanaged::<T>().is_some() {
+                self.start_managed(&instance)?;
+            }
+        }
+
+        Ok(instance)
+    }
+
+    /// Starts a cached instance and registers it for shutdown.
+    fn start_managed<T>(&self, instance: &Arc<T>) -> Result<(), BuilderError>
+    where
+        T: Lifecycle + 'static,
+    {
+        instance.start().map_err(BuilderError::resolving::<T>)?;
+
+        self.managed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((type_name::<T>(), instance.clone()));
+        if let Some(cached) = self
+            .instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&TypeId::of::<T>())
+        {
+            cached.managed = true;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the slot of `T` if it was cached by [`InstanceBuilder::build_shared`] without
+    /// being started.
+    fn unmanaged<T: 'static>(&self) -> Option<Arc<OnceSlot<SharedInstance>>> {
+        self.instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&TypeId::of::<T>())
+            .filter(|cached| !cached.managed)
+            .map(|cached| cached.slot.clone())
+    }
+
+    /// Shuts down all instances started by [`InstanceBuilder::build_managed`] in reverse order
+    /// of their creation, so instances are shut down before their dependencies.
+    ///
+    /// All instances are shut down even if some fail, the errors are combined. The instances
+    /// stay cached.
+    pub fn shutdown(&self) -> Result<(), BuilderError> {
+        let errors = self
+            .take_managed()
+            .into_iter()
+            .filter_map(|(ty, instance)| {
+                instance.shutdown().err().map(|err| shutdown_error(ty, err))
+            })
+            .collect::<Vec<_>>();
+
+        into_result(errors)
+    }
+
+    /// Async variant of [`InstanceBuilder::shutdown`], awaiting [`Lifecycle::shutdown_async`] of
+    /// one instance after another.
+    pub async fn shutdown_async(&self) -> Result<(), BuilderError> {
+        let mut errors = Vec::new();
+
+        for (ty, instance) in self.take_managed() {
+            if let Err(err) = instance.shutdown_async().await {
+                errors.push(shutdown_error(ty, err));
+            }
+        }
+
+        into_result(errors)
+    }
+
+    fn take_managed(&self) -> Vec<Managed> {
+        let mut managed = match self.parent {
+            Some(parent) => return parent.take_managed(),
+            None => {
+                std::mem::take(&mut *self.managed.lock().unwrap_or_else(PoisonError::into_inner))
+            }
+        };
+
+        managed.reverse();
+        managed
+    }
+}
+
+fn shutdown_error(ty: &'static str, err: BuilderError) -> BuilderError {
+    BuilderError::Resolution {
+        path: vec![ty.to_string()],
+        source: Box::new(err),
+    }
+}
+
+fn into_result(errors: Vec<BuilderError>) -> Result<(), BuilderError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(BuilderError::from_errors(errors))
+    }
+}
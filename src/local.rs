@@ -0,0 +1,231 @@
+use crate::stack::BuildGuard;
+use crate::{BuilderError, RegistrationError};
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Single threaded counterpart of [`InstanceBuilder`](crate::InstanceBuilder) for data that is
+/// not `Send` or `Sync`, e.g. `Rc`, `RefCell` or handles of GUI toolkits.
+///
+/// Types are built by implementing [`FromLocalInstanceBuilder`].
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use ::instancebuilder::{BuilderError, FromLocalInstanceBuilder, LocalInstanceBuilder};
+///
+/// struct Window {
+///     title: Rc<RefCell<String>>,
+/// }
+///
+/// impl FromLocalInstanceBuilder for Window {
+///     fn try_from_local_builder(builder: &LocalInstanceBuilder) -> Result<Self, BuilderError> {
+///         Ok(Self {
+///             title: builder.data::<Rc<RefCell<String>>>()?.clone(),
+///         })
+///     }
+/// }
+///
+/// let mut builder = LocalInstanceBuilder::new();
+/// builder.insert(Rc::new(RefCell::new(String::from("help me!"))));
+///
+/// let window = builder.build::<Window>().unwrap();
+/// ```
+#[derive(Default)]
+pub struct LocalInstanceBuilder {
+    data: HashMap<TypeId, Box<dyn Any>>,
+    once: HashMap<TypeId, RefCell<Option<Box<dyn Any>>>>,
+    instances: RefCell<HashMap<TypeId, Rc<dyn Any>>>,
+}
+
+impl LocalInstanceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts data, returning the data previously registered for `D`.
+    pub fn insert<D: Any>(&mut self, data: D) -> Option<D> {
+        self.data
+            .insert(TypeId::of::<D>(), Box::new(data))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Inserts data unless data of type `D` is already registered, handing the data back
+    /// otherwise.
+    pub fn try_insert<D: Any>(&mut self, data: D) -> Result<(), RegistrationError<D>> {
+        if self.data.contains_key(&TypeId::of::<D>()) {
+            return Err(RegistrationError::new::<D>(data));
+        }
+
+        self.data.insert(TypeId::of::<D>(), Box::new(data));
+
+        Ok(())
+    }
+
+    pub fn data<D: Any>(&self) -> Result<&D, BuilderError> {
+        self.data_opt()
+            .ok_or_else(|| BuilderError::DataDoesNotExist {
+                ty: type_name::<D>().to_string(),
+            })
+    }
+
+    pub fn data_opt<D: Any>(&self) -> Option<&D> {
+        self.data
+            .get(&TypeId::of::<D>())
+            .and_then(|d| d.downcast_ref::<D>())
+    }
+
+    pub fn data_mut<D: Any>(&mut self) -> Result<&mut D, BuilderError> {
+        self.data
+            .get_mut(&TypeId::of::<D>())
+            .and_then(|d| d.downcast_mut::<D>())
+            .ok_or_else(|| BuilderError::DataDoesNotExist {
+                ty: type_name::<D>().to_string(),
+            })
+    }
+
+    /// Removes the data of type `D`, returning it.
+    pub fn remove<D: Any>(&mut self) -> Option<D> {
+        self.data
+            .remove(&TypeId::of::<D>())
+            .and_then(|data| data.downcast().ok())
+            .map(|data| *data)
+    }
+
+    /// Inserts data that can be taken out once by [`LocalInstanceBuilder::take`].
+    pub fn insert_once<D: Any>(&mut self, data: D) {
+        self.once
+            .insert(TypeId::of::<D>(), RefCell::new(Some(Box::new(data))));
+    }
+
+    /// Takes the data inserted by [`LocalInstanceBuilder::insert_once`] out of the builder, see
+    /// [`InstanceBuilder::take`](crate::InstanceBuilder::take).
+    pub fn take<D: Any>(&self) -> Result<D, BuilderError> {
+        let once =
+            self.once
+                .get(&TypeId::of::<D>())
+                .ok_or_else(|| BuilderError::DataDoesNotExist {
+                    ty: type_name::<D>().to_string(),
+                })?;
+
+        once.borrow_mut()
+            .take()
+            .and_then(|data| data.downcast().ok())
+            .map(|data| *data)
+            .ok_or_else(|| BuilderError::AlreadyTaken {
+                ty: type_name::<D>().to_string(),
+            })
+    }
+
+    /// Builds a new instance of `T`, see [`InstanceBuilder::build`](crate::InstanceBuilder::build).
+    pub fn build<T>(&self) -> Result<T, BuilderError>
+    where
+        T: FromLocalInstanceBuilder + 'static,
+    {
+        let _guard = BuildGuard::enter::<T>()?;
+
+        T::try_from_local_builder(self).map_err(BuilderError::resolving::<T>)
+    }
+
+    /// Returns the shared instance of `T`, building it on first use.
+    pub fn build_shared<T>(&self) -> Result<Rc<T>, BuilderError>
+    where
+        T: FromLocalInstanceBuilder + 'static,
+    {
+        let cached = self.instances.borrow().get(&TypeId::of::<T>()).cloned();
+        if let Some(instance) = cached {
+            return Ok(instance
+                .downcast()
+                .expect("shared instance is stored by its own type id"));
+        }
+
+        // Not borrowed while building, nested builds may add their own instances.
+        let instance = Rc::new(self.build::<T>()?);
+        self.instances
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), instance.clone());
+
+        Ok(instance)
+    }
+}
+
+pub trait FromLocalInstanceBuilder: Sized {
+    fn try_from_local_builder(builder: &LocalInstanceBuilder) -> Result<Self, BuilderError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FromLocalInstanceBuilder, LocalInstanceBuilder};
+    use crate::BuilderError;
+    use std::any::type_name;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Counter {
+        count: Rc<RefCell<usize>>,
+    }
+
+    impl FromLocalInstanceBuilder for Counter {
+        fn try_from_local_builder(builder: &LocalInstanceBuilder) -> Result<Self, BuilderError> {
+            let count = builder.data::<Rc<RefCell<usize>>>()?.clone();
+            *count.borrow_mut() += 1;
+            Ok(Self { count })
+        }
+    }
+
+    struct Loop;
+
+    impl FromLocalInstanceBuilder for Loop {
+        fn try_from_local_builder(builder: &LocalInstanceBuilder) -> Result<Self, BuilderError> {
+            builder.build_shared::<Loop>()?;
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn it_builds_from_non_send_data() {
+        let count = Rc::new(RefCell::new(0));
+        let mut builder = LocalInstanceBuilder::new();
+        builder.insert(count.clone());
+
+        builder.build::<Counter>().unwrap();
+        let shared = builder.build_shared::<Counter>().unwrap();
+
+        assert!(Rc::ptr_eq(&shared, &builder.build_shared().unwrap()));
+        assert!(Rc::ptr_eq(&shared.count, &count));
+        assert_eq!(*count.borrow(), 2);
+    }
+
+    #[test]
+    fn it_modifies_removes_and_takes_data() {
+        let mut builder = LocalInstanceBuilder::new();
+        builder.insert(Rc::new(1usize));
+        builder.insert_once(Rc::new(String::from("once")));
+
+        *builder.data_mut::<Rc<usize>>().unwrap() = Rc::new(2);
+
+        assert_eq!(builder.remove::<Rc<usize>>().as_deref(), Some(&2));
+        assert!(builder.data::<Rc<usize>>().is_err());
+        assert_eq!(*builder.take::<Rc<String>>().unwrap(), "once");
+        assert!(matches!(
+            builder.take::<Rc<String>>(),
+            Err(BuilderError::AlreadyTaken { .. })
+        ));
+    }
+
+    #[test]
+    fn it_detects_cycles_and_missing_data() {
+        let builder = LocalInstanceBuilder::new();
+
+        assert!(matches!(
+            builder.build::<Loop>(),
+            Err(BuilderError::CyclicDependency { path }) if path.len() == 2
+        ));
+        assert!(matches!(
+            builder.build::<Counter>().err().unwrap().root(),
+            BuilderError::DataDoesNotExist { ty } if ty == type_name::<Rc<RefCell<usize>>>()
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{BuilderError, InstanceBuilder};
+use std::any::{type_name, Any, TypeId};
+
+/// Group of registrations, installed with [`InstanceBuilder::install`].
+///
+/// ```
+/// use ::instancebuilder::{BuilderError, InstanceBuilder, Module};
+///
+/// struct DatabaseModule;
+///
+/// impl Module for DatabaseModule {
+///     fn configure(&self, builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+///         Ok(builder.try_insert(String::from("postgres://localhost"))?)
+///     }
+/// }
+///
+/// struct AppModule;
+///
+/// impl Module for AppModule {
+///     fn configure(&self, builder: &mut InstanceBuilder) -> Result<(), BuilderError> {
+///         Ok(builder.try_insert(8080u16)?)
+///     }
+///
+///     fn dependencies(&self) -> Vec<Box<dyn Module>> {
+///         vec![Box::new(DatabaseModule)]
+///     }
+/// }
+///
+/// let mut builder = InstanceBuilder::new();
+/// builder.install(AppModule).unwrap();
+/// assert_eq!(builder.data::<String>().unwrap(), "postgres://localhost");
+/// ```
+pub trait Module: Any {
+    /// Registers the data, factories and bindings of the module.
+    ///
+    /// Prefer [`InstanceBuilder::try_insert`] over [`InstanceBuilder::insert`], so registering a
+    /// type twice is reported as an error naming the module instead of a panic in strict mode.
+    fn configure(&self, builder: &mut InstanceBuilder<'_>) -> Result<(), BuilderError>;
+
+    /// Modules installed before this module, unless they are installed already.
+    fn dependencies(&self) -> Vec<Box<dyn Module>> {
+        Vec::new()
+    }
+
+    /// Name of the module in errors, defaults to the type name.
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+impl InstanceBuilder<'_> {
+    /// Installs the module after its dependencies.
+    ///
+    /// Every module type is installed once, installing a module that is already installed in
+    /// this builder or one of its parents does nothing. Errors of a module are wrapped in a
+    /// [`BuilderError::Module`] naming it, dependencies on each other fail with a
+    /// [`BuilderError::CyclicDependency`].
+    pub fn install(&mut self, module: impl Module) -> Result<(), BuilderError> {
+        self.install_module(&module, &mut Vec::new())
+    }
+
+    /// Returns whether the module type `M` has been installed in this builder or a parent.
+    pub fn is_installed<M: Module>(&self) -> bool {
+        self.has_module(TypeId::of::<M>())
+    }
+
+    fn has_module(&self, id: TypeId) -> bool {
+        self.modules.contains(&id) || self.parent.is_some_and(|parent| parent.has_module(id))
+    }
+
+    fn install_module(
+        &mut self,
+        module: &dyn Module,
+        installing: &mut Vec<(TypeId, &'static str)>,
+    ) -> Result<(), BuilderError> {
+        let id = Any::type_id(module);
+
+        if let Some(pos) = installing
+            .iter()
+            .position(|(installing, _)| *installing == id)
+        {
+            let path = installing[pos..]
+                .iter()
+                .map(|(_, name)| name.to_string())
+                .chain([module.name().to_string()])
+                .collect();
+            return Err(BuilderError::CyclicDependency { path });
+        }
+        if self.has_module(id) {
+            return Ok(());
+        }
+
+        installing.push((id, module.name()));
+        for dependency in module.dependencies() {
+            self.install_module(dependency.as_ref(), installing)?;
+        }
+        installing.pop();
+
+        module
+            .configure(self)
+            .map_err(|source| BuilderError::Module {
+                name: module.name().to_string(),
+                source: Box::new(source),
+            })?;
+        self.modules.insert(id);
+
+        Ok(())
+    }
+}
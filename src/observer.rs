@@ -0,0 +1,177 @@
+use crate::{BuilderError, DependencyKind};
+#[cfg(feature = "graph")]
+use std::cell::RefCell;
+#[cfg(feature = "graph")]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "graph")]
+thread_local! {
+    /// Observer notified in addition to the observer of the builder, see [`observe`].
+    static SCOPED: RefCell<Option<Arc<dyn BuildObserver>>> = const { RefCell::new(None) };
+}
+
+/// Receives notifications about the builds of an [`InstanceBuilder`](crate::InstanceBuilder),
+/// e.g. to measure the startup time of services.
+///
+/// Registered with [`InstanceBuilder::set_observer`](crate::InstanceBuilder::set_observer). With
+/// the `tracing` feature enabled, builds are additionally traced by `build` spans carrying the
+/// type name in the `ty` field.
+pub trait BuildObserver: Send + Sync {
+    /// Called before `ty` is built.
+    fn build_started(&self, ty: &'static str) {
+        let _ = ty;
+    }
+
+    /// Called after building `ty` completed, successful or not.
+    fn build_finished(
+        &self,
+        ty: &'static str,
+        elapsed: Duration,
+        result: Result<(), &BuilderError>,
+    ) {
+        let _ = (ty, elapsed, result);
+    }
+
+    /// Called if a cached instance of `ty` is returned instead of building it.
+    fn cache_hit(&self, ty: &'static str) {
+        let _ = ty;
+    }
+
+    /// Called when data of type `ty` is looked up, `found` tells whether it exists. `kind` is
+    /// [`DependencyKind::Optional`] for lookups that don't require the data, like
+    /// [`InstanceBuilder::data_opt`](crate::InstanceBuilder::data_opt), and
+    /// [`DependencyKind::Data`] otherwise.
+    ///
+    /// Named data, the values of `data_all`, bindings of `resolve` and the data of `take` are
+    /// reported as lookups of their type as well.
+    fn data_lookup(&self, ty: &'static str, kind: DependencyKind, found: bool) {
+        let _ = (ty, kind, found);
+    }
+}
+
+impl<O: BuildObserver + ?Sized> BuildObserver for std::sync::Arc<O> {
+    fn build_started(&self, ty: &'static str) {
+        (**self).build_started(ty);
+    }
+
+    fn build_finished(
+        &self,
+        ty: &'static str,
+        elapsed: Duration,
+        result: Result<(), &BuilderError>,
+    ) {
+        (**self).build_finished(ty, elapsed, result);
+    }
+
+    fn cache_hit(&self, ty: &'static str) {
+        (**self).cache_hit(ty);
+    }
+
+    fn data_lookup(&self, ty: &'static str, kind: DependencyKind, found: bool) {
+        (**self).data_lookup(ty, kind, found);
+    }
+}
+
+/// Reports a single build to the observer and the tracing subscriber.
+pub(crate) struct Observation<'o> {
+    observer: Option<&'o dyn BuildObserver>,
+    ty: &'static str,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'o> Observation<'o> {
+    pub(crate) fn start(observer: Option<&'o dyn BuildObserver>, ty: &'static str) -> Self {
+        notify(observer, |observer| observer.build_started(ty));
+
+        Self {
+            observer,
+            ty,
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "build",
+                ty,
+                success = tracing::field::Empty,
+                elapsed_us = tracing::field::Empty,
+            ),
+        }
+    }
+
+    /// Runs `f` within the span of the build.
+    pub(crate) fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.enter();
+
+        f()
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    pub(crate) fn finish<R>(self, result: &Result<R, BuilderError>) {
+        let elapsed = self.start.elapsed();
+
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("success", result.is_ok());
+            self.span.record("elapsed_us", elapsed.as_micros() as u64);
+            if let Err(err) = result {
+                self.span
+                    .in_scope(|| tracing::debug!(error = %err, "build failed"));
+            }
+        }
+
+        notify(self.observer, |observer| {
+            observer.build_finished(self.ty, elapsed, result.as_ref().map(drop))
+        });
+    }
+}
+
+pub(crate) fn cache_hit(observer: Option<&dyn BuildObserver>, ty: &'static str) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(ty, "cache hit");
+
+    notify(observer, |observer| observer.cache_hit(ty));
+}
+
+pub(crate) fn data_lookup(
+    observer: Option<&dyn BuildObserver>,
+    ty: &'static str,
+    kind: DependencyKind,
+    found: bool,
+) {
+    notify(observer, |observer| observer.data_lookup(ty, kind, found));
+}
+
+/// Notifies `observer` in addition to the observer of each builder while `f` runs on this
+/// thread.
+#[cfg(feature = "graph")]
+pub(crate) fn observe<R>(observer: Arc<dyn BuildObserver>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn BuildObserver>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED.with(|scoped| *scoped.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(SCOPED.with(|scoped| scoped.borrow_mut().replace(observer)));
+
+    f()
+}
+
+fn notify(observer: Option<&dyn BuildObserver>, f: impl Fn(&dyn BuildObserver)) {
+    if let Some(observer) = observer {
+        f(observer);
+    }
+    // Cloned out of the cell, the observer may trigger lookups itself.
+    #[cfg(feature = "graph")]
+    if let Some(scoped) = SCOPED.with(|scoped| scoped.borrow().clone()) {
+        f(scoped.as_ref());
+    }
+}
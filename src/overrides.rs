@@ -0,0 +1,177 @@
+use crate::{DataEntry, Dependency, DependencyKind, InstanceBuilder};
+use std::any::{Any, TypeId};
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::PoisonError;
+
+/// Replaces the data of type `D` until it is dropped, created by
+/// [`InstanceBuilder::override_guard`].
+///
+/// The builder is available through `Deref` and `DerefMut`. On drop, the previous data is
+/// restored and the instances depending on the override are dropped from the caches.
+pub struct OverrideGuard<'b, 'a, D: Any + Send + Sync> {
+    builder: &'b mut InstanceBuilder<'a>,
+    previous: Option<DataEntry>,
+    data: PhantomData<D>,
+}
+
+impl<'a> InstanceBuilder<'a> {
+    /// Replaces the data of type `D` with `value` while `f` runs, e.g. with a mock in tests.
+    ///
+    /// See [`InstanceBuilder::override_guard`].
+    ///
+    /// ```
+    /// use ::instancebuilder::{BuilderError, FromInstanceBuilder, InstanceBuilder};
+    ///
+    /// struct Greeting(String);
+    ///
+    /// impl FromInstanceBuilder for Greeting {
+    ///     fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+    ///         Ok(Self(format!("hello {}", builder.data::<String>()?)))
+    ///     }
+    /// }
+    ///
+    /// let mut builder = InstanceBuilder::new();
+    /// builder.insert(String::from("world"));
+    ///
+    /// builder.with_override(String::from("mock"), |builder| {
+    ///     assert_eq!(builder.build_shared::<Greeting>().unwrap().0, "hello mock");
+    /// });
+    /// assert_eq!(builder.build_shared::<Greeting>().unwrap().0, "hello world");
+    /// ```
+    pub fn with_override<D, R>(&mut self, value: D, f: impl FnOnce(&mut Self) -> R) -> R
+    where
+        D: Any + Send + Sync,
+    {
+        let mut guard = self.override_guard(value);
+        f(&mut guard)
+    }
+
+    /// Replaces the data of type `D` with `value` until the returned guard is dropped.
+    ///
+    /// The override applies to this builder only, also in strict mode. When the override is set
+    /// and when it is restored, instances cached by [`InstanceBuilder::build_shared`] and data
+    /// created by factories are dropped from the caches if they depend on `D`, so they get
+    /// rebuilt with the current data. Dependencies are taken from
+    /// [`FromInstanceBuilder::dependencies`](crate::FromInstanceBuilder::dependencies) and
+    /// [`InstanceBuilder::register_factory_with_dependencies`], types and factories declaring
+    /// no dependencies are always dropped.
+    ///
+    /// Instances started by [`InstanceBuilder::build_managed`] are running services and stay
+    /// cached, even if they depend on `D`.
+    pub fn override_guard<D>(&mut self, value: D) -> OverrideGuard<'_, 'a, D>
+    where
+        D: Any + Send + Sync,
+    {
+        let previous = self
+            .data
+            .insert(TypeId::of::<D>(), DataEntry::Value(Box::new(value)));
+        self.invalidate_dependents(TypeId::of::<D>());
+
+        OverrideGuard {
+            builder: self,
+            previous,
+            data: PhantomData,
+        }
+    }
+
+    /// Drops the cached instances and factory data depending on `overridden`.
+    fn invalidate_dependents(&mut self, overridden: TypeId) {
+        let instances = self
+            .instances
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut stale = HashSet::from([overridden]);
+
+        // Repeated until nothing changes, as instances may depend on data of factories and the
+        // other way around.
+        loop {
+            let found = stale.len();
+
+            for (id, entry) in &self.data {
+                if let DataEntry::Factory { dependencies, .. } = entry {
+                    let depends = dependencies
+                        .as_deref()
+                        .is_none_or(|dependencies| reaches(dependencies, &stale));
+                    if depends {
+                        stale.insert(*id);
+                    }
+                }
+            }
+
+            for (id, cached) in instances.iter() {
+                let dependencies = (cached.dependencies)();
+                if !cached.managed && (dependencies.is_empty() || reaches(&dependencies, &stale)) {
+                    stale.insert(*id);
+                }
+            }
+
+            if stale.len() == found {
+                break;
+            }
+        }
+
+        instances.retain(|id, cached| cached.managed || !stale.contains(id));
+
+        for (id, entry) in &mut self.data {
+            if let DataEntry::Factory { value, .. } = entry {
+                if stale.contains(id) {
+                    *value = Default::default();
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether the dependencies reach one of the `stale` types, nested builds without
+/// declared dependencies are assumed to reach them.
+fn reaches(dependencies: &[Dependency], stale: &HashSet<TypeId>) -> bool {
+    fn visit(
+        dependencies: &[Dependency],
+        stale: &HashSet<TypeId>,
+        visited: &mut HashSet<TypeId>,
+    ) -> bool {
+        dependencies.iter().any(|dependency| {
+            if stale.contains(&dependency.type_id()) {
+                return true;
+            }
+            if dependency.kind() != DependencyKind::Build || !visited.insert(dependency.type_id()) {
+                return false;
+            }
+
+            let nested = dependency.dependencies();
+            nested.is_empty() || visit(&nested, stale, visited)
+        })
+    }
+
+    visit(dependencies, stale, &mut HashSet::new())
+}
+
+impl<'a, D: Any + Send + Sync> Deref for OverrideGuard<'_, 'a, D> {
+    type Target = InstanceBuilder<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.builder
+    }
+}
+
+impl<D: Any + Send + Sync> DerefMut for OverrideGuard<'_, '_, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.builder
+    }
+}
+
+impl<D: Any + Send + Sync> Drop for OverrideGuard<'_, '_, D> {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => {
+                self.builder.data.insert(TypeId::of::<D>(), previous);
+            }
+            None => {
+                self.builder.data.remove(&TypeId::of::<D>());
+            }
+        }
+        self.builder.invalidate_dependents(TypeId::of::<D>());
+    }
+}
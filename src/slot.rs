@@ -0,0 +1,154 @@
+use crate::BuilderError;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError};
+use std::thread::{self, ThreadId};
+
+/// Lazily initialized value which is initialized at most once, even under concurrent access.
+///
+/// In contrast to a plain [`OnceLock`], the initializer is fallible. A failed initialization
+/// leaves the slot empty, so the next caller tries again.
+pub(crate) struct OnceSlot<V> {
+    value: OnceLock<V>,
+    init: Mutex<()>,
+}
+
+impl<V> OnceSlot<V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            value: OnceLock::new(),
+            init: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&V> {
+        self.value.get()
+    }
+
+    pub(crate) fn get_mut(&mut self) -> Option<&mut V> {
+        self.value.get_mut()
+    }
+
+    pub(crate) fn into_inner(self) -> Option<V> {
+        self.value.into_inner()
+    }
+
+    /// Returns the value, initializing it with `f` if the slot is empty.
+    ///
+    /// Waiting for the initialization of another thread fails with
+    /// [`BuilderError::CyclicDependency`] if that thread (indirectly) waits for a slot this
+    /// thread initializes. `name` names the value in the cycle.
+    pub(crate) fn get_or_try_init<F>(&self, name: &'static str, f: F) -> Result<&V, BuilderError>
+    where
+        F: FnOnce() -> Result<V, BuilderError>,
+    {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+
+        let _lock = self.lock()?;
+
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+
+        let _init = Initializing::enter(self.key(), name);
+        let value = f()?;
+
+        Ok(self.value.get_or_init(|| value))
+    }
+}
+
+impl<V> OnceSlot<V> {
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Blocks the initialization of the slot until the guard is dropped, waiting like
+    /// [`OnceSlot::get_or_try_init`].
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, ()>, BuilderError> {
+        // A panicking initializer did not store anything, the lock can be reused safely.
+        match self.init.try_lock() {
+            Ok(lock) => Ok(lock),
+            Err(TryLockError::Poisoned(err)) => Ok(err.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                let _waiting = Waiting::enter(self.key())?;
+                Ok(self.init.lock().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+}
+
+/// Slots being initialized by a thread and the slots threads are waiting for, across all
+/// builders.
+#[derive(Default)]
+struct Inits {
+    owners: HashMap<usize, (ThreadId, &'static str)>,
+    waiting: HashMap<ThreadId, usize>,
+}
+
+static INITS: LazyLock<Mutex<Inits>> = LazyLock::new(Default::default);
+
+fn inits() -> MutexGuard<'static, Inits> {
+    INITS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Marks a slot as being initialized by the current thread until dropped.
+struct Initializing(usize);
+
+impl Initializing {
+    fn enter(key: usize, name: &'static str) -> Self {
+        inits().owners.insert(key, (thread::current().id(), name));
+        Self(key)
+    }
+}
+
+impl Drop for Initializing {
+    fn drop(&mut self) {
+        inits().owners.remove(&self.0);
+    }
+}
+
+/// Marks the current thread as waiting for a slot until dropped.
+struct Waiting;
+
+impl Waiting {
+    /// Fails if waiting for the slot would wait for the current thread itself.
+    fn enter(key: usize) -> Result<Self, BuilderError> {
+        let current = thread::current().id();
+        let mut inits = inits();
+
+        // Follows the slots the owners are waiting for, a cycle of other threads is detected by
+        // those threads.
+        let mut path = Vec::new();
+        let mut slot = key;
+        while let Some(&(owner, name)) = inits.owners.get(&slot) {
+            path.push(name.to_string());
+
+            if owner == current {
+                let mut cycle = vec![name.to_string()];
+                cycle.extend(path);
+                return Err(BuilderError::CyclicDependency { path: cycle });
+            }
+
+            match inits.waiting.get(&owner) {
+                Some(&next) if path.len() <= inits.owners.len() => slot = next,
+                _ => break,
+            }
+        }
+
+        inits.waiting.insert(current, key);
+        Ok(Self)
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        inits().waiting.remove(&thread::current().id());
+    }
+}
+
+impl<V> Default for OnceSlot<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::BuilderError;
+use std::any::{type_name, TypeId};
+use std::cell::RefCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Types currently under construction on this thread, outermost first.
+    static BUILD_STACK: RefCell<Vec<(TypeId, &'static str)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a type as being under construction until dropped.
+///
+/// Entering a type that is already under construction on the same thread fails with
+/// [`BuilderError::CyclicDependency`] instead of recursing endlessly.
+pub(crate) struct BuildGuard {
+    // The stack is thread local, the guard must be dropped on the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Marks the factory of `D` on the stack, apart from builds of `D` itself, so that a factory
+/// may build its own type.
+struct FactoryOf<D>(PhantomData<D>);
+
+impl BuildGuard {
+    pub(crate) fn enter<T: 'static>() -> Result<Self, BuilderError> {
+        Self::enter_id(TypeId::of::<T>(), type_name::<T>())
+    }
+
+    /// Marks the factory of `D` as running until dropped.
+    pub(crate) fn enter_factory<D: 'static>() -> Result<Self, BuilderError> {
+        Self::enter_id(TypeId::of::<FactoryOf<D>>(), type_name::<D>())
+    }
+
+    /// Returns whether the factory of `D` is running on this thread.
+    pub(crate) fn in_factory<D: 'static>() -> bool {
+        let id = TypeId::of::<FactoryOf<D>>();
+
+        BUILD_STACK.with(|stack| stack.borrow().iter().any(|(entry, _)| *entry == id))
+    }
+
+    /// Marks `T` as being under construction while `future` is polled.
+    ///
+    /// Async builds may move between threads and interleave with other tasks, so the stack of
+    /// the calling build is captured and restored on every poll instead of being kept on the
+    /// thread.
+    pub(crate) fn track<T: 'static, F: Future>(future: F) -> Result<Tracked<F>, BuilderError> {
+        let _guard = Self::enter::<T>()?;
+        let stack = BUILD_STACK.with(|stack| stack.borrow().clone());
+
+        Ok(Tracked {
+            stack,
+            future: Box::pin(future),
+        })
+    }
+
+    fn enter_id(id: TypeId, name: &'static str) -> Result<Self, BuilderError> {
+        BUILD_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+
+            if let Some(pos) = stack.iter().position(|(entry, _)| *entry == id) {
+                let path = stack[pos..]
+                    .iter()
+                    .map(|(_, name)| name.to_string())
+                    .chain(Some(name.to_string()))
+                    .collect();
+
+                return Err(BuilderError::CyclicDependency { path });
+            }
+
+            stack.push((id, name));
+
+            Ok(Self {
+                _not_send: PhantomData,
+            })
+        })
+    }
+}
+
+impl Drop for BuildGuard {
+    fn drop(&mut self) {
+        BUILD_STACK.with(|stack| {
+            stack.borrow_mut().pop();
+        });
+    }
+}
+
+/// Future of an async build, see [`BuildGuard::track`].
+pub(crate) struct Tracked<F> {
+    stack: Vec<(TypeId, &'static str)>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let restore = Restore::swap(&mut this.stack);
+
+        let result = this.future.as_mut().poll(cx);
+        drop(restore);
+
+        result
+    }
+}
+
+/// Swaps a captured stack with the stack of the current thread until dropped.
+struct Restore<'a>(&'a mut Vec<(TypeId, &'static str)>);
+
+impl<'a> Restore<'a> {
+    fn swap(stack: &'a mut Vec<(TypeId, &'static str)>) -> Self {
+        BUILD_STACK.with(|current| std::mem::swap(&mut *current.borrow_mut(), stack));
+
+        Self(stack)
+    }
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        BUILD_STACK.with(|current| std::mem::swap(&mut *current.borrow_mut(), self.0));
+    }
+}
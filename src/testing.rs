@@ -0,0 +1,398 @@
+use crate::{BuildObserver, BuilderError, DependencyKind, FromInstanceBuilder, InstanceBuilder};
+use std::any::{type_name, Any, TypeId};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Lookup recorded by a [`TestBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    /// Data of type `ty` was requested, `found` tells whether it existed.
+    Data { ty: &'static str, found: bool },
+    /// An instance of `ty` was built or returned from a cache.
+    Build { ty: &'static str, success: bool },
+}
+
+impl Lookup {
+    /// Returns the name of the looked up type.
+    pub fn ty(&self) -> &'static str {
+        match self {
+            Lookup::Data { ty, .. } | Lookup::Build { ty, .. } => ty,
+        }
+    }
+}
+
+/// [`InstanceBuilder`] recording the data and builds looked up through it, created by
+/// [`InstanceBuilder::for_test`].
+///
+/// The builder is available through `Deref` and `DerefMut`. Replacing its observer stops the
+/// recording.
+///
+/// ```
+/// use ::instancebuilder::{BuilderError, FromInstanceBuilder, InstanceBuilder};
+///
+/// #[derive(Default)]
+/// struct Config {
+///     retries: u8,
+/// }
+///
+/// struct Client {
+///     retries: u8,
+/// }
+///
+/// impl FromInstanceBuilder for Client {
+///     fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+///         Ok(Self {
+///             retries: builder.data::<Config>()?.retries,
+///         })
+///     }
+/// }
+///
+/// let mut builder = InstanceBuilder::for_test();
+/// builder.fill_default::<Config>();
+///
+/// assert_eq!(builder.build::<Client>().unwrap().retries, 0);
+/// builder.assert_touched::<Config>();
+/// builder.assert_built::<Client>();
+/// ```
+pub struct TestBuilder {
+    builder: InstanceBuilder<'static>,
+    recorder: Arc<Recorder>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    lookups: Mutex<Vec<Lookup>>,
+}
+
+impl Recorder {
+    fn record(&self, lookup: Lookup) {
+        self.lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(lookup);
+    }
+}
+
+impl BuildObserver for Recorder {
+    fn build_finished(
+        &self,
+        ty: &'static str,
+        _elapsed: Duration,
+        result: Result<(), &BuilderError>,
+    ) {
+        self.record(Lookup::Build {
+            ty,
+            success: result.is_ok(),
+        });
+    }
+
+    fn cache_hit(&self, ty: &'static str) {
+        self.record(Lookup::Build { ty, success: true });
+    }
+
+    fn data_lookup(&self, ty: &'static str, _kind: DependencyKind, found: bool) {
+        self.record(Lookup::Data { ty, found });
+    }
+}
+
+impl InstanceBuilder<'static> {
+    /// Creates a builder for tests, see [`TestBuilder`].
+    pub fn for_test() -> TestBuilder {
+        let recorder = Arc::new(Recorder::default());
+        let mut builder = InstanceBuilder::new();
+        builder.set_observer(recorder.clone());
+
+        TestBuilder { builder, recorder }
+    }
+}
+
+impl TestBuilder {
+    /// Inserts `D::default()` unless data of type `D` is registered already.
+    ///
+    /// Whether a type implements `Default` can't be detected at runtime, so missing data is not
+    /// filled automatically. Call this for each type a test should fill, [`TestBuilder::missing`]
+    /// lists the candidates after a failed build.
+    pub fn fill_default<D: Default + Any + Send + Sync>(&mut self) -> &mut Self {
+        if !self.builder.contains(TypeId::of::<D>()) {
+            self.builder.insert(D::default());
+        }
+        self
+    }
+
+    /// Returns the lookups recorded so far, in the order they happened.
+    ///
+    /// Builds are recorded when they finish, after the lookups of their dependencies.
+    pub fn lookups(&self) -> Vec<Lookup> {
+        self.recorder
+            .lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Forgets the lookups recorded so far.
+    pub fn clear_lookups(&self) {
+        self.recorder
+            .lookups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Returns the types of the data that was looked up but did not exist.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        for lookup in self.lookups() {
+            if let Lookup::Data { ty, found: false } = lookup {
+                if !missing.contains(&ty) {
+                    missing.push(ty);
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Returns whether data of type `D` or an instance of `D` was looked up.
+    pub fn touched<D: ?Sized>(&self) -> bool {
+        self.lookups()
+            .iter()
+            .any(|lookup| lookup.ty() == type_name::<D>())
+    }
+
+    /// Asserts that data of type `D` or an instance of `D` was looked up.
+    #[track_caller]
+    pub fn assert_touched<D: ?Sized>(&self) {
+        assert!(
+            self.touched::<D>(),
+            "{} was not looked up, lookups: {:?}",
+            type_name::<D>(),
+            self.lookups()
+        );
+    }
+
+    /// Asserts that neither data of type `D` nor an instance of `D` was looked up.
+    #[track_caller]
+    pub fn assert_not_touched<D: ?Sized>(&self) {
+        assert!(
+            !self.touched::<D>(),
+            "{} was looked up, lookups: {:?}",
+            type_name::<D>(),
+            self.lookups()
+        );
+    }
+
+    /// Asserts that an instance of `T` was built successfully or returned from a cache.
+    #[track_caller]
+    pub fn assert_built<T: FromInstanceBuilder>(&self) {
+        let built = self.lookups().contains(&Lookup::Build {
+            ty: type_name::<T>(),
+            success: true,
+        });
+        assert!(
+            built,
+            "{} was not built, lookups: {:?}",
+            type_name::<T>(),
+            self.lookups()
+        );
+    }
+
+    /// Returns the builder without recording further lookups.
+    pub fn into_inner(self) -> InstanceBuilder<'static> {
+        let mut builder = self.builder;
+        builder.observer = None;
+        builder
+    }
+}
+
+impl Deref for TestBuilder {
+    type Target = InstanceBuilder<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.builder
+    }
+}
+
+impl DerefMut for TestBuilder {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lookup;
+    use crate::{BuilderError, FromInstanceBuilder, InstanceBuilder, Key};
+    use std::any::type_name;
+
+    #[derive(Default)]
+    struct Retries(u8);
+
+    struct Endpoint(String);
+
+    struct Client {
+        retries: u8,
+        endpoint: Option<String>,
+    }
+
+    impl FromInstanceBuilder for Client {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self {
+                retries: builder.data::<Retries>()?.0,
+                endpoint: builder.data_opt::<Endpoint>().map(|e| e.0.clone()),
+            })
+        }
+    }
+
+    struct Service {
+        client: Client,
+    }
+
+    impl FromInstanceBuilder for Service {
+        fn try_from_builder(builder: &InstanceBuilder) -> Result<Self, BuilderError> {
+            Ok(Self {
+                client: builder.build()?,
+            })
+        }
+    }
+
+    #[test]
+    fn it_records_lookups() {
+        let mut builder = InstanceBuilder::for_test();
+        assert!(builder.build::<Service>().is_err());
+        assert_eq!(builder.missing(), vec![type_name::<Retries>()]);
+
+        builder.clear_lookups();
+        builder.fill_default::<Retries>();
+        let service = builder.build::<Service>().unwrap();
+
+        assert_eq!(service.client.retries, 0);
+        assert_eq!(service.client.endpoint, None);
+        assert_eq!(
+            builder.lookups(),
+            vec![
+                Lookup::Data {
+                    ty: type_name::<Retries>(),
+                    found: true
+                },
+                Lookup::Data {
+                    ty: type_name::<Endpoint>(),
+                    found: false
+                },
+                Lookup::Build {
+                    ty: type_name::<Client>(),
+                    success: true
+                },
+                Lookup::Build {
+                    ty: type_name::<Service>(),
+                    success: true
+                },
+            ]
+        );
+        builder.assert_built::<Client>();
+        builder.assert_touched::<Endpoint>();
+        builder.assert_not_touched::<String>();
+    }
+
+    #[test]
+    fn it_keeps_existing_data_when_filling_defaults() {
+        let mut builder = InstanceBuilder::for_test();
+        builder.insert(Retries(3));
+        builder.fill_default::<Retries>();
+
+        assert_eq!(builder.build::<Client>().unwrap().retries, 3);
+
+        let builder = builder.into_inner();
+        assert!(builder.build_shared::<Client>().is_ok());
+    }
+
+    #[test]
+    fn it_records_named_lookups() {
+        const PRIMARY: Key<String> = Key::new("primary");
+
+        let mut builder = InstanceBuilder::for_test();
+        builder.insert_key(&PRIMARY, String::from("primary"));
+
+        assert!(builder.data_named_opt::<u8>("replica").is_none());
+        assert!(builder.data_key(&PRIMARY).is_ok());
+
+        assert_eq!(
+            builder.lookups(),
+            vec![
+                Lookup::Data {
+                    ty: "u8",
+                    found: false
+                },
+                Lookup::Data {
+                    ty: type_name::<String>(),
+                    found: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_records_collection_lookups() {
+        let mut builder = InstanceBuilder::for_test();
+        builder.insert_many(Retries(1));
+
+        assert_eq!(builder.data_all::<Retries>().count(), 1);
+        assert_eq!(builder.data_all::<Endpoint>().count(), 0);
+
+        assert_eq!(builder.missing(), vec![type_name::<Endpoint>()]);
+        builder.assert_touched::<Retries>();
+    }
+
+    trait Transport: Send + Sync {}
+
+    impl Transport for Client {}
+
+    #[test]
+    fn it_records_binding_lookups() {
+        let mut builder = InstanceBuilder::for_test();
+        assert!(builder.resolve::<dyn Transport>().is_err());
+        assert_eq!(builder.missing(), vec![type_name::<dyn Transport>()]);
+
+        builder.clear_lookups();
+        builder.fill_default::<Retries>();
+        builder.bind::<dyn Transport, Client>(|client| client);
+        assert!(builder.resolve::<dyn Transport>().is_ok());
+
+        assert!(builder.lookups().contains(&Lookup::Data {
+            ty: type_name::<dyn Transport>(),
+            found: true
+        }));
+        builder.assert_built::<Client>();
+    }
+
+    #[test]
+    fn it_records_taken_data() {
+        let mut builder = InstanceBuilder::for_test();
+        builder.insert_once(Endpoint(String::from("localhost")));
+
+        assert_eq!(builder.take::<Endpoint>().unwrap().0, "localhost");
+        assert!(builder.take::<Endpoint>().is_err());
+
+        assert_eq!(
+            builder.lookups(),
+            vec![
+                Lookup::Data {
+                    ty: type_name::<Endpoint>(),
+                    found: true
+                },
+                Lookup::Data {
+                    ty: type_name::<Endpoint>(),
+                    found: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "was not looked up")]
+    fn it_fails_assertions_on_untouched_types() {
+        let builder = InstanceBuilder::for_test();
+        builder.assert_touched::<Retries>();
+    }
+}